@@ -1,39 +1,148 @@
-use super::Processor;
-use crate::data::messages::{BookRecord, BookUpdate, RecordUpdate};
+use super::{Processor, PushProcessor};
+use crate::data::book::TradePairs;
+use crate::data::markets::MarketRegistry;
+use crate::data::messages::{BookRecord, BookUpdate, PushMessage, RecordUpdate};
+use crate::data::sequence::{is_initial, Sequencer, DEFAULT_WINDOW};
 use crate::data::trade::TradeBook;
 use crate::error::PoloError;
+use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
 pub struct Accountant {
     tb: Arc<Mutex<TradeBook>>,
+    sequencer: Sequencer,
+    lenient: bool,
+    registry: Option<Arc<MarketRegistry>>,
+    // updates of registered books received before their initial book, at most window per book
+    early: HashMap<u16, VecDeque<BookUpdate>>,
 }
 
 impl Accountant {
     pub fn new(tb: Arc<Mutex<TradeBook>>) -> Accountant {
-        Accountant { tb }
+        Accountant::with_window(tb, DEFAULT_WINDOW)
     }
-}
 
-impl Processor for Accountant {
-    fn process_message(&mut self, msg: String) -> Result<(), PoloError> {
-        let err = |title| PoloError::wrong_data(format!("{} {:?}", title, msg));
+    // window is the number of out of order updates held per book before a gap is reported
+    pub fn with_window(tb: Arc<Mutex<TradeBook>>, window: usize) -> Accountant {
+        Accountant {
+            tb,
+            sequencer: Sequencer::new(window),
+            lenient: false,
+            registry: None,
+            early: HashMap::new(),
+        }
+    }
+
+    // markets to label book ids with pairs and to resolve numeric channels on resync,
+    // updates of registered books arriving ahead of initial book are held until it comes
+    pub fn set_registry(&mut self, registry: Arc<MarketRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    // skip records which fail to parse instead of rejecting the whole update
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    // drop books of resubscribed channels after reconnect,
+    // their updates are rejected until fresh RecordUpdate::Initial
+    pub fn resync(&mut self, channels: &[String]) {
+        let registry = self.registry.as_ref();
+        let pairs: Vec<TradePairs> = channels
+            .iter()
+            .filter_map(|channel| match registry.and_then(|r| r.resolve(channel)) {
+                Some(market) => Some(market.pair.clone()),
+                None => TradePairs::from_str(channel).ok(),
+            })
+            .collect();
+        for id in self.tb.lock().unwrap().invalidate(&pairs) {
+            self.sequencer.forget(id);
+            self.early.remove(&id);
+        }
+    }
+
+    // updates are applied in record_id order, a sequence gap invalidates the book
+    // until its next RecordUpdate::Initial
+    fn process_update(&mut self, update: BookUpdate) -> Result<(), PoloError> {
+        let book_id = update.book_id;
+        let updates = match self.sequencer.push(update) {
+            Ok(updates) => updates,
+            Err(err) => {
+                self.tb.lock().unwrap().invalidate_id(book_id);
+                return Err(err);
+            }
+        };
+        for update in updates {
+            if self.is_early(&update) {
+                self.hold(update);
+                continue;
+            }
+            // the rest of the batch is already taken from sequencer, book can't be trusted
+            if let Err(err) = self.apply(update) {
+                self.drop_book(book_id);
+                return Err(err);
+            }
+            // initial book arrived, held updates it does not cover are sequenced after it
+            if let Some(early) = self.early.remove(&book_id) {
+                for update in early {
+                    self.process_update(update)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // forget book after failed update, it is rebuilt from the next initial book
+    fn drop_book(&mut self, book_id: u16) {
+        self.tb.lock().unwrap().invalidate_id(book_id);
+        self.sequencer.forget(book_id);
+        self.early.remove(&book_id);
+    }
+
+    // update of registered book which initial book is not received yet
+    fn is_early(&self, update: &BookUpdate) -> bool {
+        let registered = match self.registry {
+            Some(ref registry) => registry.get(update.book_id).is_some(),
+            None => false,
+        };
+        registered
+            && !is_initial(update)
+            && self.tb.lock().unwrap().book_by_id(update.book_id).is_none()
+    }
+
+    fn hold(&mut self, update: BookUpdate) {
+        let window = self.sequencer.window();
+        let early = self.early.entry(update.book_id).or_default();
+        early.push_back(update);
+        // the oldest are the most likely to be covered by initial book
+        while early.len() > window {
+            early.pop_front();
+        }
+    }
+
+    fn apply(&mut self, update: BookUpdate) -> Result<(), PoloError> {
+        let book_id = update.book_id;
+        let pair = self.registry.as_ref().and_then(|r| r.pair(book_id));
+        let err = |title| match &pair {
+            Some(pair) => PoloError::wrong_data(format!("{} {} {}", title, book_id, pair)),
+            None => PoloError::wrong_data(format!("{} {}", title, book_id)),
+        };
 
-        let update = BookUpdate::from_str(&msg)?;
         for rec in update.records {
             let mut tb = self.tb.lock().unwrap();
             match rec {
                 RecordUpdate::Initial(book) => {
                     tb.add_book(book, update.book_id);
                 }
-                RecordUpdate::SellTotal(BookRecord { rate, amount }) => {
+                RecordUpdate::SellTotal(BookRecord { rate, amount, .. }) => {
                     let book = tb
                         .book_by_id(update.book_id)
                         .ok_or_else(|| err("book not initialized"))?;
                     book.update_sell_orders(rate, amount);
                 }
-                RecordUpdate::BuyTotal(BookRecord { rate, amount }) => {
+                RecordUpdate::BuyTotal(BookRecord { rate, amount, .. }) => {
                     let book = tb
                         .book_by_id(update.book_id)
                         .ok_or_else(|| err("book not initialized"))?;
@@ -43,26 +152,52 @@ impl Processor for Accountant {
                     let book = tb
                         .book_by_id(update.book_id)
                         .ok_or_else(|| err("book not initialized"))?;
-                    book.new_deal(deal.id, deal.rate, -deal.amount)?;
+                    let time = deal.precise_time();
+                    book.new_deal(deal.id, deal.rate, -deal.amount, time)?;
                 }
                 RecordUpdate::Buy(deal) => {
                     let book = tb
                         .book_by_id(update.book_id)
                         .ok_or_else(|| err("book not initialized"))?;
-                    book.new_deal(deal.id, deal.rate, deal.amount)?;
+                    let time = deal.precise_time();
+                    book.new_deal(deal.id, deal.rate, deal.amount, time)?;
                 }
+                // lenient mode only, the rest of update is still applied
+                RecordUpdate::Unknown(_) => (),
             }
         }
         Ok(())
     }
 }
 
+impl Processor for Accountant {
+    fn process_message(&mut self, msg: String) -> Result<(), PoloError> {
+        let update = if self.lenient {
+            BookUpdate::from_str_lenient(&msg)?
+        } else {
+            BookUpdate::from_str(&msg)?
+        };
+        self.process_update(update)
+    }
+}
+
+impl PushProcessor for Accountant {
+    fn process(&mut self, msg: PushMessage) -> Result<(), PoloError> {
+        match msg {
+            PushMessage::Book(update) => self.process_update(update),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Accountant;
-    use crate::actors::Processor;
-    use crate::data::messages::{BookUpdate, RecordUpdate};
+    use crate::actors::{Processor, PushProcessor};
+    use crate::data::markets::MarketRegistry;
+    use crate::data::messages::{BookUpdate, PushMessage, RecordUpdate};
     use crate::data::trade::TradeBook;
+    use crate::error::PoloError;
     use std::str::FromStr;
     use std::sync::{Arc, Mutex};
 
@@ -70,15 +205,210 @@ mod tests {
     fn initial_order() {
         let tb = Arc::new(Mutex::new(TradeBook::new()));
         let mut accountant = Accountant::new(tb.clone());
-        let order = String::from(r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13169621": 0.2331}]}]]]"#);
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13169621": 0.2331}]}]]]"#,
+        );
 
         accountant.process_message(order.clone()).unwrap();
 
         let mut tb_mut = tb.lock().unwrap();
         let actor_book = tb_mut.book_by_id(189).unwrap().book_ref();
         match BookUpdate::from_str(&order).unwrap().records[0] {
-            RecordUpdate::Initial(ref book) => assert_eq!((&book.sell, &book.buy), (&actor_book.sell, &actor_book.buy)),
+            RecordUpdate::Initial(ref book) => {
+                assert_eq!((&book.sell, &book.buy), (&actor_book.sell, &actor_book.buy))
+            }
             _ => panic!("BookUpdate::from_str were not able to parse RecordUpdate::Initial"),
         }
     }
+
+    #[test]
+    fn resync_drops_book() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let update = String::from(r#"[189,5130996,[["o",1,"0.12906425","0.02691207"]]]"#);
+
+        accountant.process_message(order.clone()).unwrap();
+        accountant.resync(&["BTC_BCH".to_owned()]);
+
+        assert!(tb.lock().unwrap().book_by_id(189).is_none());
+        assert!(accountant.process_message(update).is_err());
+        accountant.process_message(order).unwrap();
+        assert!(tb.lock().unwrap().book_by_id(189).is_some());
+    }
+
+    #[test]
+    fn registry_labels_and_resyncs() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let registry = MarketRegistry::from_str(
+            r#"[{"id":189,"pair":"BTC_BCH","baseId":28,"quoteId":292,"active":true,"frozen":false}]"#,
+        )
+        .unwrap();
+        accountant.set_registry(Arc::new(registry));
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let update = String::from(r#"[189,5130996,[["o",1,"0.1316","0.1"]]]"#);
+
+        match accountant.process_message(update.replace("189", "190")) {
+            Err(err) => assert!(err.to_string().contains("book not initialized 190")),
+            res => panic!("expected book not initialized {:?}", res),
+        }
+        accountant.process_message(order).unwrap();
+        accountant.resync(&["189".to_owned()]);
+        assert!(tb.lock().unwrap().book_by_id(189).is_none());
+    }
+
+    #[test]
+    fn registry_holds_early_updates() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let registry = MarketRegistry::from_str(
+            r#"[{"id":189,"pair":"BTC_BCH","baseId":28,"quoteId":292,"active":true,"frozen":false}]"#,
+        )
+        .unwrap();
+        accountant.set_registry(Arc::new(registry));
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let update = |id, rate| format!(r#"[189,{},[["o",1,"{}","0.1"]]]"#, id, rate);
+
+        // covered by initial book and ahead of it
+        accountant
+            .process_message(update(5130994, "0.1315"))
+            .unwrap();
+        accountant
+            .process_message(update(5130996, "0.1316"))
+            .unwrap();
+        assert!(tb.lock().unwrap().book_by_id(189).is_none());
+        accountant.process_message(order).unwrap();
+        accountant
+            .process_message(update(5130997, "0.1317"))
+            .unwrap();
+
+        let mut tb = tb.lock().unwrap();
+        let book = tb.book_by_id(189).unwrap().book_ref();
+        assert!(!book.buy.contains_key("0.1315"));
+        assert_eq!(book.buy["0.1316"], 0.1);
+        assert_eq!(book.buy["0.1317"], 0.1);
+    }
+
+    #[test]
+    fn gap_invalidates_book() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::with_window(tb.clone(), 1);
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let update = |id| format!(r#"[189,{},[["o",1,"0.1316","0.1"]]]"#, id);
+
+        accountant.process_message(order.clone()).unwrap();
+        accountant.process_message(update(5130997)).unwrap();
+        assert!(tb.lock().unwrap().book_by_id(189).is_some());
+        match accountant.process_message(update(5130998)) {
+            Err(PoloError::SequenceGap(189, 5130996, 5130997)) => (),
+            res => panic!("expected sequence gap {:?}", res),
+        }
+        assert!(tb.lock().unwrap().book_by_id(189).is_none());
+        accountant.process_message(update(5130996)).unwrap();
+        accountant.process_message(order).unwrap();
+        accountant.process_message(update(5130996)).unwrap();
+        let mut tb = tb.lock().unwrap();
+        assert_eq!(tb.book_by_id(189).unwrap().book_ref().buy["0.1316"], 0.1);
+    }
+
+    #[test]
+    fn failed_update_drops_book() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let trade =
+            String::from(r#"[189,5130996,[["t","714116",0,"rate","0.05946471",1504163848]]]"#);
+        let update = |id| format!(r#"[189,{},[["o",1,"0.1316","0.1"]]]"#, id);
+
+        accountant.process_message(order.clone()).unwrap();
+        accountant.process_message(update(5130997)).unwrap();
+        // 5130996 fails and takes 5130997 along, the book is incomplete
+        assert!(accountant.process_message(trade).is_err());
+        assert!(tb.lock().unwrap().book_by_id(189).is_none());
+        assert!(accountant.process_message(update(5130998)).is_err());
+        accountant.process_message(order).unwrap();
+        accountant.process_message(update(5130996)).unwrap();
+        let mut tb = tb.lock().unwrap();
+        assert_eq!(tb.book_by_id(189).unwrap().book_ref().buy["0.1316"], 0.1);
+    }
+
+    #[test]
+    fn typed_messages() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let msgs = vec![
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+            "[1010]",
+            r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#,
+            r#"[189,5130996,[["o",1,"0.1316","0.1"]]]"#,
+        ];
+
+        for msg in msgs {
+            accountant
+                .process(PushMessage::from_str(msg).unwrap())
+                .unwrap();
+        }
+        let mut tb = tb.lock().unwrap();
+        assert_eq!(tb.book_by_id(189).unwrap().book_ref().buy["0.1316"], 0.1);
+    }
+
+    #[test]
+    fn lenient_skips_unknown() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let update = String::from(r#"[189,5130996,[["x",1],["o",1,"0.1316","0.1"]]]"#);
+
+        accountant.process_message(order).unwrap();
+        assert!(accountant.process_message(update.clone()).is_err());
+        accountant.set_lenient(true);
+        accountant.process_message(update).unwrap();
+        let mut tb = tb.lock().unwrap();
+        assert_eq!(tb.book_by_id(189).unwrap().book_ref().buy["0.1316"], 0.1);
+    }
+
+    #[test]
+    fn deal_exchange_time() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        let order = String::from(
+            r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#,
+        );
+        let trade = String::from(
+            r#"[189,5130996,[["t","714116",0,"0.12906425","0.05946471",1504163848]]]"#,
+        );
+        let precise = String::from(
+            r#"[189,5130997,[["t","714117",1,"0.12906425","0.1",1504163849,"1504163849250"]]]"#,
+        );
+
+        accountant.process_message(order).unwrap();
+        accountant.process_message(trade).unwrap();
+        accountant.process_message(precise).unwrap();
+
+        let mut tb_mut = tb.lock().unwrap();
+        let deals = &tb_mut.book_by_id(189).unwrap().book_ref().deals;
+        // newest deal first
+        let deal = &deals.data[1];
+        assert_eq!((deal.id, deal.amount), (714_116, -0.059_464_71));
+        assert_eq!(deal.exchange_time.sec, 1_504_163_848);
+        assert!(deal.time > deal.exchange_time);
+        let deal = &deals.data[0];
+        assert_eq!(
+            (deal.exchange_time.sec, deal.exchange_time.nsec),
+            (1_504_163_849, 250_000_000)
+        );
+    }
 }
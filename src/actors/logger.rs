@@ -1,4 +1,5 @@
-use super::Processor;
+use super::{Processor, PushProcessor};
+use crate::data::messages::PushMessage;
 use crate::error::PoloError;
 use crate::get_time;
 
@@ -12,3 +13,11 @@ impl Processor for Logger {
         Ok(())
     }
 }
+
+impl PushProcessor for Logger {
+    fn process(&mut self, msg: PushMessage) -> Result<(), PoloError> {
+        let ts = get_time();
+        println!("{}.{} {:?}", ts.sec, ts.nsec / 1_000_000, msg);
+        Ok(())
+    }
+}
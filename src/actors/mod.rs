@@ -1,14 +1,25 @@
 pub mod book;
+pub mod ticker;
+pub mod v3;
 
 pub use book::Accountant;
+pub use ticker::TickerKeeper;
+pub use v3::V3Accountant;
 
 #[cfg(not(target_arch="wasm32"))]
 pub mod logger;
 #[cfg(not(target_arch="wasm32"))]
 pub use logger::Logger;
 
+use crate::data::messages::PushMessage;
 use crate::error::PoloError;
 
 pub trait Processor {
     fn process_message(&mut self, msg: String) -> Result<(), PoloError>;
 }
+
+// processor of already parsed messages, ex: from push::typed stream,
+// messages of channels processor is not interested in are skipped
+pub trait PushProcessor {
+    fn process(&mut self, msg: PushMessage) -> Result<(), PoloError>;
+}
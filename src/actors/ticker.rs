@@ -0,0 +1,53 @@
+use super::{Processor, PushProcessor};
+use crate::data::messages::{PushMessage, TickerUpdate};
+use crate::data::ticker::TickerTable;
+use crate::error::PoloError;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct TickerKeeper {
+    table: Arc<Mutex<TickerTable>>,
+}
+
+impl TickerKeeper {
+    pub fn new(table: Arc<Mutex<TickerTable>>) -> TickerKeeper {
+        TickerKeeper { table }
+    }
+}
+
+impl Processor for TickerKeeper {
+    fn process_message(&mut self, msg: String) -> Result<(), PoloError> {
+        let ticker = TickerUpdate::from_str(&msg)?;
+        self.table.lock().unwrap().update(ticker);
+        Ok(())
+    }
+}
+
+impl PushProcessor for TickerKeeper {
+    fn process(&mut self, msg: PushMessage) -> Result<(), PoloError> {
+        if let PushMessage::Ticker(ticker) = msg {
+            self.table.lock().unwrap().update(ticker);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TickerKeeper;
+    use crate::actors::Processor;
+    use crate::data::ticker::TickerTable;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn ticker_update() {
+        let table = Arc::new(Mutex::new(TickerTable::new()));
+        let mut keeper = TickerKeeper::new(table.clone());
+        let ticker = String::from(r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",1,"412.25844455","373.79204003"]]"#);
+
+        keeper.process_message(ticker).unwrap();
+
+        assert!(table.lock().unwrap().get_by_id(149).unwrap().is_frozen);
+    }
+}
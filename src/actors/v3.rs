@@ -0,0 +1,192 @@
+use super::Processor;
+use crate::data::book::TradePairs;
+use crate::data::messages::{BookRecord, RecordUpdate};
+use crate::data::trade::TradeBook;
+use crate::data::v3::{V3BookUpdate, V3Message, V3Trade};
+use crate::error::PoloError;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+// Accountant for v3 api frames, books are kept in the same TradeBook addressed by pair
+#[derive(Clone)]
+pub struct V3Accountant {
+    tb: Arc<Mutex<TradeBook>>,
+    // id of the last applied book_lv2 update per pair, absent until snapshot
+    last_ids: HashMap<TradePairs, u64>,
+}
+
+impl V3Accountant {
+    pub fn new(tb: Arc<Mutex<TradeBook>>) -> V3Accountant {
+        V3Accountant {
+            tb,
+            last_ids: HashMap::new(),
+        }
+    }
+
+    // drop books of resubscribed pairs after reconnect, their updates are rejected until snapshot
+    pub fn resync(&mut self, pairs: &[TradePairs]) {
+        let mut tb = self.tb.lock().unwrap();
+        for pair in pairs {
+            self.last_ids.remove(pair);
+            if let Some(book) = tb.book_by_pair(pair) {
+                book.reset_orders();
+            }
+        }
+    }
+
+    fn apply_book(&mut self, update: V3BookUpdate) -> Result<(), PoloError> {
+        let mut tb = self.tb.lock().unwrap();
+        let is_snapshot = update
+            .records
+            .iter()
+            .any(|rec| matches!(rec, RecordUpdate::Initial(_)));
+        if !is_snapshot {
+            let last_id = match self.last_ids.get(&update.pair) {
+                Some(last_id) => *last_id,
+                None => {
+                    return Err(PoloError::wrong_data(format!(
+                        "book not initialized {}",
+                        update.pair
+                    )))
+                }
+            };
+            // duplicate or already covered by snapshot
+            if update.id <= last_id {
+                return Ok(());
+            }
+            if update.last_id != last_id {
+                self.last_ids.remove(&update.pair);
+                if let Some(book) = tb.book_by_pair(&update.pair) {
+                    book.reset_orders();
+                }
+                return Err(PoloError::wrong_data(format!(
+                    "book_lv2 sequence gap {} expected lastId {} got {}",
+                    update.pair, last_id, update.last_id
+                )));
+            }
+        }
+        self.last_ids.insert(update.pair.clone(), update.id);
+        for rec in update.records {
+            match rec {
+                RecordUpdate::Initial(book) => {
+                    tb.add_pair_book(book);
+                }
+                RecordUpdate::SellTotal(BookRecord { rate, amount, .. }) => {
+                    if let Some(book) = tb.book_by_pair(&update.pair) {
+                        book.update_sell_orders(rate, amount);
+                    }
+                }
+                RecordUpdate::BuyTotal(BookRecord { rate, amount, .. }) => {
+                    if let Some(book) = tb.book_by_pair(&update.pair) {
+                        book.update_buy_orders(rate, amount);
+                    }
+                }
+                // book_lv2 carries no other records
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_trade(&mut self, trade: V3Trade) -> Result<(), PoloError> {
+        let mut tb = self.tb.lock().unwrap();
+        let book = tb
+            .book_by_pair(&trade.pair)
+            .ok_or_else(|| PoloError::wrong_data(format!("book not initialized {}", trade.pair)))?;
+        match trade.record {
+            RecordUpdate::Sell(deal) => {
+                let time = deal.precise_time();
+                book.new_deal(deal.id, deal.rate, -deal.amount, time)?;
+            }
+            RecordUpdate::Buy(deal) => {
+                let time = deal.precise_time();
+                book.new_deal(deal.id, deal.rate, deal.amount, time)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Processor for V3Accountant {
+    // book_lv2 updates are applied in id order, a gap drops the book until next snapshot;
+    // events and channels other than book_lv2 and trades are ignored
+    fn process_message(&mut self, msg: String) -> Result<(), PoloError> {
+        match V3Message::from_str(&msg)? {
+            V3Message::Books(updates) => {
+                for update in updates {
+                    self.apply_book(update)?;
+                }
+            }
+            V3Message::Trades(trades) => {
+                for trade in trades {
+                    self.apply_trade(trade)?;
+                }
+            }
+            V3Message::Error(err) => return Err(PoloError::wrong_data(err)),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::V3Accountant;
+    use crate::actors::Processor;
+    use crate::data::book::TradePairs;
+    use crate::data::trade::TradeBook;
+    use std::sync::{Arc, Mutex};
+
+    const SNAPSHOT: &str = r#"{"channel":"book_lv2","action":"snapshot","data":[{"symbol":"BTC_USDT","createTime":1652774727337,"asks":[["40001","0.1"]],"bids":[["40000.1","0.5"]],"lastId":10,"id":11,"ts":1652774727344}]}"#;
+
+    fn update(last_id: u64, id: u64) -> String {
+        format!(
+            r#"{{"channel":"book_lv2","action":"update","data":[{{"symbol":"BTC_USDT","createTime":1652774727337,"asks":[["40002","0.3"]],"bids":[["40000.1","0"]],"lastId":{},"id":{},"ts":1652774727350}}]}}"#,
+            last_id, id
+        )
+    }
+
+    #[test]
+    fn book_and_deals() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = V3Accountant::new(tb.clone());
+        accountant.process_message(SNAPSHOT.to_owned()).unwrap();
+        accountant.process_message(update(11, 12)).unwrap();
+        accountant.process_message(update(11, 12)).unwrap();
+        let trade = r#"{"channel":"trades","data":[{"symbol":"BTC_USDT","amount":"20000","takerSide":"sell","quantity":"0.5","createTime":1652774727360,"price":"40000","id":"194","ts":1652774727370}]}"#;
+        accountant.process_message(trade.to_owned()).unwrap();
+
+        let mut tb = tb.lock().unwrap();
+        let book = tb.book_by_pair(&TradePairs::UsdtBtc).unwrap().book_ref();
+        assert_eq!(book.sell.len(), 2);
+        assert_eq!(book.sell["40002"], 0.3);
+        assert!(book.buy.is_empty());
+        assert_eq!(book.deals.data.len(), 1);
+        assert_eq!(
+            (book.deals.data[0].id, book.deals.data[0].amount),
+            (194, -0.5)
+        );
+    }
+
+    #[test]
+    fn gap_drops_book() {
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = V3Accountant::new(tb.clone());
+        assert!(accountant.process_message(update(11, 12)).is_err());
+        accountant.process_message(SNAPSHOT.to_owned()).unwrap();
+        assert!(accountant.process_message(update(12, 13)).is_err());
+        assert!(tb
+            .lock()
+            .unwrap()
+            .book_by_pair(&TradePairs::UsdtBtc)
+            .unwrap()
+            .book_ref()
+            .sell
+            .is_empty());
+        assert!(accountant.process_message(update(13, 14)).is_err());
+        accountant.process_message(SNAPSHOT.to_owned()).unwrap();
+        accountant.process_message(update(11, 12)).unwrap();
+    }
+}
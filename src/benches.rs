@@ -6,6 +6,7 @@ extern crate test;
 
 #[cfg(test)]
 mod tests {
+  use crate::data::borrowed::BookUpdateRef;
   use crate::data::messages::BookUpdate;
   use std::str::FromStr;
   use test::Bencher;
@@ -23,6 +24,12 @@ mod tests {
     b.iter(|| BookUpdate::from_str(&order));
   }
 
+  #[bench]
+  fn json_read_order_updates_borrowed(b: &mut Bencher) {
+    let order = r#"[189,4811424,[["o",1,"0.12906425","0.02691207"],["t","714116",0,"0.12906425","0.05946471",1504163848]]]"#;
+    b.iter(|| BookUpdateRef::parse(&order).map(|update| update.records().count()));
+  }
+
   #[bench]
   fn json_read_order_updates_json(b: &mut Bencher) {
     let order = r#"[189,4811424,[["o",1,"0.12906425","0.02691207"],["t","714116",0,"0.12906425","0.05946471",1504163848]]]"#;
@@ -1,33 +1,30 @@
-// TODO: Convert to serde deserialization!
-use super::json::Expect;
+use super::json::{Items, Num};
+pub use super::pair::CurrencyPair;
 use super::timeseries::{Timeseries, WithTime};
 use crate::error::PoloError;
+use crate::get_time;
 use json::JsonValue;
-use serde::Serialize;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::str::FromStr;
 use time::Timespec;
-use crate::get_time;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub enum TradePairs {
-    BtcEth,
-    BtcBch,
-    BtcLtc,
-    BtcZec,
-    UsdtBtc,
-    UsdtEth,
-    UsdtLtc,
-    UsdtBch,
-    UsdtZec,
-    UsdtXrp,
-}
+// the enum of ten markets grew into CurrencyPair, its variants are kept as constants
+pub type TradePairs = CurrencyPair;
 
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Deal {
+    // local time deal was received at
     #[serde(with = "serialize_timespec")]
     pub time: Timespec,
+    // time deal happened at exchange, milliseconds resolution if exchange sent epoch_ms
+    #[serde(with = "serialize_timespec")]
+    pub exchange_time: Timespec,
+    // exchange trade id
     pub id: u64,
     pub rate: f64,
     pub amount: f64, // amount < 0 means sell order reconciled, otherwise buy
@@ -71,7 +68,13 @@ pub trait BookAccounting: Debug {
     fn update_buy_orders(&mut self, rate: String, amount: f64) -> Option<f64>;
 
     // should return rate parse result to f64, or error wrapped in PoloError
-    fn new_deal(&mut self, id: u64, rate: String, amount: f64) -> Result<f64, PoloError>;
+    fn new_deal(
+        &mut self,
+        id: u64,
+        rate: String,
+        amount: f64,
+        exchange_time: Timespec,
+    ) -> Result<f64, PoloError>;
 
     // reference to the actual Book struct (for wrappers)
     fn book_ref(&self) -> &Book;
@@ -119,12 +122,19 @@ impl BookAccounting for Book {
             self.buy.insert(rate, amount)
         }
     }
-    fn new_deal(&mut self, id: u64, rate: String, amount: f64) -> Result<f64, PoloError> {
+    fn new_deal(
+        &mut self,
+        id: u64,
+        rate: String,
+        amount: f64,
+        exchange_time: Timespec,
+    ) -> Result<f64, PoloError> {
         self.last_updated = get_time();
         let rate = rate.parse().map_err(PoloError::from)?;
         let time = get_time();
         self.deals.add(Deal {
             time,
+            exchange_time,
             id,
             rate,
             amount,
@@ -144,31 +154,138 @@ impl BookAccounting for Book {
 /**
  * Book conversion traits
  * use:
- *  let val = match json::parse(r#"["i", {"currencyPair": "BTC_BCH", "orderBook": [{0.13161901: "0.23709568", 0.13164313: "0.17328089"}, {0.13169621: "0.2331"}]]"#) {
- *     json::Array(vec) => vec,
- *     _ => Err(())
- *  };
- *  let records:: Book = Book::try_from(&val)
+ *  let book:: Book = Book::from_str(
+ *    r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13169621": "0.2331"}]}"#
+ *  )
  **/
 
-impl<'a> TryFrom<&'a JsonValue> for Book {
-    type Error = PoloError;
-    fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
-        let err = |msg| Err(PoloError::wrong_data(format!("{} {:?}", msg, v)));
+impl FromStr for Book {
+    type Err = PoloError;
+    fn from_str(book: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(book).map_err(PoloError::from)
+    }
+}
+
+// [{sell rate: amount}, {buy rate: amount}]
+struct OrderBook(Records, Records);
+
+impl<'de> Deserialize<'de> for OrderBook {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderBookVisitor;
+        impl<'de> Visitor<'de> for OrderBookVisitor {
+            type Value = OrderBook;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("initial book orderBook array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<OrderBook, A::Error> {
+                let mut items =
+                    Items::new(seq, "initial book orderBook array should contain 2 objects");
+                let sell: HashMap<String, Num<f64>> = items.next("initial book orderBook[0]")?;
+                let buy: HashMap<String, Num<f64>> = items.next("initial book orderBook[1]")?;
+                items.end()?;
+                let records = |side: HashMap<String, Num<f64>>| {
+                    side.into_iter()
+                        .map(|(rate, amount)| (rate, amount.0))
+                        .collect()
+                };
+                Ok(OrderBook(records(sell), records(buy)))
+            }
+        }
+        deserializer.deserialize_seq(OrderBookVisitor)
+    }
+}
+
+// initial book in push api format, orders only
+// one side of orderBook in the order poloniex sends it: asks ascending by rate, bids descending
+struct Side<'a> {
+    records: &'a Records,
+    descending: bool,
+}
 
-        if !v.is_object() {
-            return err("initial book is not object");
+impl<'a> Serialize for Side<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut records: Vec<(f64, &String, &f64)> = self
+            .records
+            .iter()
+            .map(|(rate, amount)| (rate.parse().unwrap_or(f64::NAN), rate, amount))
+            .collect();
+        // unparsable rates go last, ties are ordered by rate string to keep output stable
+        records.sort_by(|a, b| {
+            let by_rate = match (a.0.is_nan(), b.0.is_nan()) {
+                (false, false) if self.descending => b.0.partial_cmp(&a.0).unwrap(),
+                (false, false) => a.0.partial_cmp(&b.0).unwrap(),
+                (nan_a, nan_b) => nan_a.cmp(&nan_b),
+            };
+            by_rate.then_with(|| a.1.cmp(b.1))
+        });
+        let mut map = serializer.serialize_map(Some(records.len()))?;
+        for (_, rate, amount) in records {
+            map.serialize_entry(rate, &Num(*amount))?;
         }
-        if v["orderBook"].len() != 2 {
-            return err("initial book orderBook array should contain 2 objects");
+        map.end()
+    }
+}
+
+impl Serialize for Book {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sell = Side {
+            records: &self.sell,
+            descending: false,
+        };
+        let buy = Side {
+            records: &self.buy,
+            descending: true,
+        };
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("currencyPair", &self.pair)?;
+        map.serialize_entry("orderBook", &(sell, buy))?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Book {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BookVisitor;
+        impl<'de> Visitor<'de> for BookVisitor {
+            type Value = Book;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("initial book object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Book, A::Error> {
+                let mut pair = None;
+                let mut order_book = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "currencyPair" => pair = Some(map.next_value()?),
+                        "orderBook" => order_book = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                let pair = pair.ok_or_else(|| de::Error::missing_field("currencyPair"))?;
+                let OrderBook(sell, buy) =
+                    order_book.ok_or_else(|| de::Error::missing_field("orderBook"))?;
+                Ok(Book {
+                    last_updated: get_time(),
+                    pair,
+                    sell,
+                    buy,
+                    deals: Timeseries::default(),
+                })
+            }
         }
+        deserializer.deserialize_map(BookVisitor)
+    }
+}
 
-        Ok(Self {
-            last_updated: get_time(),
-            pair: TradePairs::try_from(&v["currencyPair"])?,
-            sell: v["orderBook"][0].expect("initial book orderBook[0]")?,
-            buy: v["orderBook"][1].expect("initial book orderBook[1]")?,
-            deals: Timeseries::default(),
-        })
+impl<'a> TryFrom<&'a JsonValue> for Book {
+    type Error = PoloError;
+    fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
     }
 }
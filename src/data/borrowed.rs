@@ -0,0 +1,328 @@
+use super::book::Book;
+use super::json::{Items, Num};
+use super::messages::{BookRecord, BookUpdate, RecordUpdate, TradeRecord};
+use crate::error::PoloError;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::str::FromStr;
+use time::Timespec;
+
+// book update borrowing from the frame it was parsed from, records are parsed lazily
+// ex: [189,4811375,[["o",1,"0.12774723","0.00000000"],["t","714109",1,"0.12900000","1.03377186",1504163835]]]
+#[derive(Debug, Clone, Copy)]
+pub struct BookUpdateRef<'a> {
+    pub book_id: u16,
+    pub record_id: u64,
+    // records array as is
+    records: &'a str,
+}
+
+// ["o",1,"0.12774723","0.00000000"]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookRecordRef<'a> {
+    pub rate: &'a str,
+    pub amount: f64,
+    pub epoch_ms: Option<u64>,
+}
+
+// ["t","714109",1,"0.12900000","1.03377186",1504163835]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeRecordRef<'a> {
+    pub id: u64,
+    pub rate: &'a str,
+    pub amount: f64,
+    pub exchange_time: Timespec,
+    pub epoch_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordRef<'a> {
+    SellTotal(BookRecordRef<'a>),
+    BuyTotal(BookRecordRef<'a>),
+    Sell(TradeRecordRef<'a>),
+    Buy(TradeRecordRef<'a>),
+    // initial book object as is, it is rare and big so not worth borrowing
+    Initial(&'a str),
+}
+
+// iterator over records of BookUpdateRef, stops after the first error
+#[derive(Debug, Clone)]
+pub struct RecordsRef<'a> {
+    rest: &'a str,
+}
+
+impl<'a> BookUpdateRef<'a> {
+    // strings of the frame are borrowed, so escaped rates or trade ids are refused
+    pub fn parse(frame: &'a str) -> Result<Self, PoloError> {
+        serde_json::from_str(frame).map_err(PoloError::from)
+    }
+
+    pub fn records(&self) -> RecordsRef<'a> {
+        let inner = &self.records[1..self.records.len() - 1];
+        RecordsRef { rest: inner }
+    }
+
+    // owned update, the same as BookUpdate::from_str would produce
+    pub fn to_update(&self) -> Result<BookUpdate, PoloError> {
+        let records = self
+            .records()
+            .map(|rec| rec.and_then(|rec| rec.to_record()))
+            .collect::<Result<_, _>>()?;
+        Ok(BookUpdate {
+            book_id: self.book_id,
+            record_id: self.record_id,
+            records,
+        })
+    }
+}
+
+impl<'a> RecordRef<'a> {
+    pub fn to_record(&self) -> Result<RecordUpdate, PoloError> {
+        let book = |rec: &BookRecordRef| BookRecord {
+            rate: rec.rate.to_owned(),
+            amount: rec.amount,
+            epoch_ms: rec.epoch_ms,
+        };
+        let trade = |rec: &TradeRecordRef| TradeRecord {
+            id: rec.id,
+            rate: rec.rate.to_owned(),
+            amount: rec.amount,
+            exchange_time: rec.exchange_time,
+            epoch_ms: rec.epoch_ms,
+        };
+        Ok(match self {
+            RecordRef::SellTotal(rec) => RecordUpdate::SellTotal(book(rec)),
+            RecordRef::BuyTotal(rec) => RecordUpdate::BuyTotal(book(rec)),
+            RecordRef::Sell(rec) => RecordUpdate::Sell(trade(rec)),
+            RecordRef::Buy(rec) => RecordUpdate::Buy(trade(rec)),
+            RecordRef::Initial(raw) => RecordUpdate::Initial(Book::from_str(raw)?),
+        })
+    }
+}
+
+impl<'a> Iterator for RecordsRef<'a> {
+    type Item = Result<RecordRef<'a>, PoloError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (item, rest) = split_item(self.rest)?;
+        self.rest = rest;
+        let record = serde_json::from_str(item).map_err(PoloError::from);
+        if record.is_err() {
+            self.rest = "";
+        }
+        Some(record)
+    }
+}
+
+// first top level item of json array body and the rest after it, None if body is empty
+// body is already validated json, so only strings and nesting need to be tracked
+fn split_item(body: &str) -> Option<(&str, &str)> {
+    let body = body.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    if body.is_empty() {
+        return None;
+    }
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, b) in body.bytes().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => depth -= 1,
+            b',' if depth == 0 => return Some((&body[..i], &body[i + 1..])),
+            _ => (),
+        }
+    }
+    Some((body, ""))
+}
+
+/**
+ * BookUpdateRef conversion traits
+ * use:
+ *  let update:: BookUpdateRef = BookUpdateRef::parse(
+ *    r#"[189,4811375,[["o",1,"0.12774723","0.00000000"],["t","714109",1,"0.12900000","1.03377186",1504163835]]]"#
+ *  )
+ **/
+
+impl<'de: 'a, 'a> Deserialize<'de> for BookUpdateRef<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BookUpdateRefVisitor;
+        impl<'de> Visitor<'de> for BookUpdateRefVisitor {
+            type Value = BookUpdateRef<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("book update array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let mut items = Items::new(seq, "book update is not triple");
+                let book_id: Num<u16> = items.next("book update book_id")?;
+                let record_id: Num<u64> = items.next("book update record_id")?;
+                let records: &RawValue = items.next("book update records")?;
+                items.end()?;
+                let records = records.get();
+                if !records.starts_with('[') {
+                    return Err(de::Error::custom(format!(
+                        "book update records: expected array got {}",
+                        records
+                    )));
+                }
+                Ok(BookUpdateRef {
+                    book_id: book_id.0,
+                    record_id: record_id.0,
+                    records,
+                })
+            }
+        }
+        deserializer.deserialize_seq(BookUpdateRefVisitor)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RecordRef<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RecordRefVisitor;
+        impl<'de> Visitor<'de> for RecordRefVisitor {
+            type Value = RecordRef<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("book update record array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let err = |msg| Err(de::Error::custom(format!("book update record {}", msg)));
+                let mut items = Items::new(seq, "book update record has less than 2 items");
+                let kind: &str = items.next("book update record type")?;
+                let record = match kind {
+                    "o" => {
+                        items.expect_len("book record does not have 4 or 5 items");
+                        let direction: Num<u64> = items.next("book record direction")?;
+                        let record = BookRecordRef {
+                            rate: items.next("book record rate")?,
+                            amount: items.next::<Num<f64>>("book record amount")?.0,
+                            epoch_ms: items
+                                .try_next::<Num<u64>>("book record epoch_ms")?
+                                .map(|ms| ms.0),
+                        };
+                        match direction.0 {
+                            0 => RecordRef::SellTotal(record),
+                            1 => RecordRef::BuyTotal(record),
+                            _ => return err("has unknown dir"),
+                        }
+                    }
+                    "t" => {
+                        items.expect_len("trade record does not have 6 or 7 items");
+                        let id: Num<u64> = items.next("trade record id")?;
+                        let direction: Num<u64> = items.next("trade record direction")?;
+                        let rate = items.next("trade record rate")?;
+                        let amount: Num<f64> = items.next("trade record amount")?;
+                        let time: Num<u64> = items.next("trade record time")?;
+                        let epoch_ms: Option<Num<u64>> = items.try_next("trade record epoch_ms")?;
+                        let record = TradeRecordRef {
+                            id: id.0,
+                            rate,
+                            amount: amount.0,
+                            exchange_time: Timespec::new(time.0 as i64, 0),
+                            epoch_ms: epoch_ms.map(|ms| ms.0),
+                        };
+                        match direction.0 {
+                            0 => RecordRef::Sell(record),
+                            1 => RecordRef::Buy(record),
+                            _ => return err("has unknown dir"),
+                        }
+                    }
+                    "i" => {
+                        let book: &RawValue = items.next("initial book")?;
+                        items.skip()?;
+                        return Ok(RecordRef::Initial(book.get()));
+                    }
+                    _ => return err("has unknown type"),
+                };
+                items.end()?;
+                Ok(record)
+            }
+        }
+        deserializer.deserialize_seq(RecordRefVisitor)
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{split_item, BookRecordRef, BookUpdateRef, RecordRef, TradeRecordRef};
+    use crate::data::messages::RecordUpdate;
+    use time::Timespec;
+
+    #[test]
+    fn split_items() {
+        assert_eq!(split_item(""), None);
+        assert_eq!(split_item(" , "), None);
+        assert_eq!(
+            split_item(r#"["o",1],["t","a,]\"b"]"#),
+            Some((r#"["o",1]"#, r#"["t","a,]\"b"]"#))
+        );
+        assert_eq!(
+            split_item(r#"["t","a,]\"b"]"#),
+            Some((r#"["t","a,]\"b"]"#, ""))
+        );
+    }
+
+    #[test]
+    fn borrowed_records() {
+        let frame = r#"[189, 4811424, [["o",1,"0.12906425","0.02691207"], ["t","714116",0,"0.12906425",0.5,1504163848,"1504163848250"]]]"#;
+        let update = BookUpdateRef::parse(frame).unwrap();
+        assert_eq!((update.book_id, update.record_id), (189, 4_811_424));
+        let records: Vec<_> = update.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            records,
+            vec![
+                RecordRef::BuyTotal(BookRecordRef {
+                    rate: "0.12906425",
+                    amount: 0.026_912_07,
+                    epoch_ms: None
+                }),
+                RecordRef::Sell(TradeRecordRef {
+                    id: 714_116,
+                    rate: "0.12906425",
+                    amount: 0.5,
+                    exchange_time: Timespec::new(1_504_163_848, 0),
+                    epoch_ms: Some(1_504_163_848_250)
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn borrowed_to_owned() {
+        let frame = r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13169621": 0.2331}]}]]]"#;
+        let update = BookUpdateRef::parse(frame).unwrap().to_update().unwrap();
+        match update.records[0] {
+            RecordUpdate::Initial(ref book) => assert_eq!(book.buy["0.13169621"], 0.2331),
+            ref rec => panic!("expected initial book {:?}", rec),
+        }
+    }
+
+    #[test]
+    fn borrowed_errors() {
+        assert!(BookUpdateRef::parse("[189,4811424]").is_err());
+        assert!(BookUpdateRef::parse("[189,4811424,{}]").is_err());
+        let update =
+            BookUpdateRef::parse(r#"[189,4811424,[["o",3,"0.1","0.2"],["o",1,"0.1","0.2"]]]"#)
+                .unwrap();
+        let records: Vec<_> = update.records().collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+    }
+}
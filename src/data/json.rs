@@ -1,81 +1,156 @@
-use crate::error::PoloError;
-use json::JsonValue;
-use std::collections::HashMap;
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
 
 /**
- * Trait for json::JsonValue evaluation and conversion
+ * Serde helpers for poloniex heterogeneous arrays
 **/
 
-pub trait Expect<T> {
-    type Error;
-    fn expect(&self, msg: &str) -> Result<T, Self::Error>;
+// number which poloniex sends either as json number or as string, ex: "0.12900000" or 0.129
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Num<T>(pub T);
+
+pub trait FromNum: FromStr + Sized {
+    const NAME: &'static str;
+    fn from_u64(v: u64) -> Option<Self>;
+    fn from_i64(v: i64) -> Option<Self>;
+    fn from_f64(v: f64) -> Option<Self>;
 }
 
-impl Expect<f64> for JsonValue {
-    type Error = PoloError;
-    fn expect(&self, msg: &str) -> Result<f64, Self::Error> {
-        let err = || PoloError::wrong_data(format!("{}: expected float got {}", msg, self));
-        if self.is_string() {
-            self.as_str()
-                .ok_or_else(err)?
-                .parse::<f64>()
-                .map_err(PoloError::from)
-        } else {
-            self.as_f64().ok_or_else(err)
-        }
+impl FromNum for f64 {
+    const NAME: &'static str = "float";
+    fn from_u64(v: u64) -> Option<Self> {
+        Some(v as f64)
+    }
+    fn from_i64(v: i64) -> Option<Self> {
+        Some(v as f64)
+    }
+    fn from_f64(v: f64) -> Option<Self> {
+        Some(v)
     }
 }
 
-impl Expect<u16> for JsonValue {
-    type Error = PoloError;
-    fn expect(&self, msg: &str) -> Result<u16, Self::Error> {
-        let err = || PoloError::wrong_data(format!("{}: expected u16 got {}", msg, self));
-        if self.is_string() {
-            self.as_str()
-                .ok_or_else(err)?
-                .parse::<u16>()
-                .map_err(PoloError::from)
-        } else {
-            self.as_u16().ok_or_else(err)
-        }
+impl FromNum for u64 {
+    const NAME: &'static str = "u64";
+    fn from_u64(v: u64) -> Option<Self> {
+        Some(v)
+    }
+    fn from_i64(v: i64) -> Option<Self> {
+        u64::try_from(v).ok()
+    }
+    fn from_f64(_: f64) -> Option<Self> {
+        None
+    }
+}
+
+impl FromNum for u16 {
+    const NAME: &'static str = "u16";
+    fn from_u64(v: u64) -> Option<Self> {
+        u16::try_from(v).ok()
+    }
+    fn from_i64(v: i64) -> Option<Self> {
+        u16::try_from(v).ok()
+    }
+    fn from_f64(_: f64) -> Option<Self> {
+        None
     }
 }
 
-impl Expect<u64> for JsonValue {
-    type Error = PoloError;
-    fn expect(&self, msg: &str) -> Result<u64, Self::Error> {
-        let err = || PoloError::wrong_data(format!("{}: expected u64 got {}", msg, self));
-        if self.is_string() {
-            self.as_str()
-                .ok_or_else(err)?
-                .parse::<u64>()
-                .map_err(PoloError::from)
+// amounts are sent as strings with 8 decimals, more are kept if needed to read the same value back
+impl Serialize for Num<f64> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fixed = format!("{:.8}", self.0);
+        if fixed.parse() == Ok(self.0) {
+            serializer.serialize_str(&fixed)
         } else {
-            self.as_u64().ok_or_else(err)
+            serializer.serialize_str(&self.0.to_string())
         }
     }
 }
 
-impl Expect<String> for JsonValue {
-    type Error = PoloError;
-    fn expect(&self, msg: &str) -> Result<String, Self::Error> {
-        let err = || PoloError::wrong_data(format!("{}: expected string got {}", msg, self));
-        let s = self.as_str().ok_or_else(err)?;
-        Ok(String::from(s))
+struct NumVisitor<T>(PhantomData<T>);
+
+impl<T: FromNum> NumVisitor<T> {
+    fn check<E: de::Error, V: fmt::Display>(v: Option<T>, got: V) -> Result<Num<T>, E> {
+        v.map(Num)
+            .ok_or_else(|| E::custom(format!("expected {} got {}", T::NAME, got)))
     }
 }
 
-impl Expect<HashMap<String, f64>> for JsonValue {
-    type Error = PoloError;
-    fn expect(&self, msg: &str) -> Result<HashMap<String, f64>, Self::Error> {
-        if !self.is_object() {
-            return Err(PoloError::wrong_data(format!("{}: expected object", msg)));
-        }
-        let mut hash = HashMap::new();
-        for (rate, amount) in self.entries() {
-            let amount: f64 = amount.expect("expected float amount")?;
-            hash.insert(rate.to_owned(), amount);
+impl<'de, T: FromNum> Visitor<'de> for NumVisitor<T> {
+    type Value = Num<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} as number or string", T::NAME)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Num<T>, E> {
+        Self::check(T::from_u64(v), v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Num<T>, E> {
+        Self::check(T::from_i64(v), v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Num<T>, E> {
+        Self::check(T::from_f64(v), v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Num<T>, E> {
+        Self::check(v.parse().ok(), format!("{:?}", v))
+    }
+}
+
+impl<'de, T: FromNum> Deserialize<'de> for Num<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NumVisitor(PhantomData))
+    }
+}
+
+// array read item by item, errors are prefixed with title of the item
+pub struct Items<A> {
+    seq: A,
+    // error reported when array has too few or too many items
+    len_msg: &'static str,
+}
+
+impl<'de, A: SeqAccess<'de>> Items<A> {
+    pub fn new(seq: A, len_msg: &'static str) -> Self {
+        Items { seq, len_msg }
+    }
+
+    // change length error once array kind is known, ex: after record type
+    pub fn expect_len(&mut self, len_msg: &'static str) {
+        self.len_msg = len_msg;
+    }
+
+    // next item or None at the end of array
+    pub fn try_next<T: Deserialize<'de>>(&mut self, msg: &str) -> Result<Option<T>, A::Error> {
+        self.seq
+            .next_element()
+            .map_err(|err| de::Error::custom(format!("{}: {}", msg, err)))
+    }
+
+    pub fn next<T: Deserialize<'de>>(&mut self, msg: &str) -> Result<T, A::Error> {
+        let len_msg = self.len_msg;
+        self.try_next(msg)?
+            .ok_or_else(|| de::Error::custom(len_msg))
+    }
+
+    // fails if array has items left
+    pub fn end(mut self) -> Result<(), A::Error> {
+        match self.seq.next_element::<IgnoredAny>()? {
+            Some(_) => Err(de::Error::custom(self.len_msg)),
+            None => Ok(()),
         }
-        Ok(hash)
+    }
+
+    // skips items left, newer api versions append fields
+    pub fn skip(mut self) -> Result<(), A::Error> {
+        while self.seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(())
     }
 }
@@ -0,0 +1,170 @@
+use super::messages::{BookUpdate, RecordUpdate};
+use std::collections::HashMap;
+use time::Timespec;
+
+// delays below are counted per millisecond, above grow by 10% per bucket
+const LINEAR_BUCKETS: i64 = 10;
+const BUCKET_GROWTH: f64 = 1.1;
+
+// histogram of delays in milliseconds, percentiles are accurate within 10%
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    // exact extremes, negative delay means local clock is behind exchange
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+// exchange-to-local delay of trades per book_id
+// without epoch_ms exchange time has seconds resolution, so delays are overestimated by up to a second
+#[derive(Clone, Debug, Default)]
+pub struct LatencyTracker {
+    by_book: HashMap<u16, Histogram>,
+}
+
+impl Histogram {
+    pub fn add(&mut self, delay_ms: i64) {
+        let bucket = bucket(delay_ms);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.total += 1;
+        self.min = Some(self.min.map_or(delay_ms, |min| min.min(delay_ms)));
+        self.max = Some(self.max.map_or(delay_ms, |max| max.max(delay_ms)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<i64> {
+        self.max
+    }
+
+    // delay not exceeded by percent of samples, e.g. percentile(99.0)
+    pub fn percentile(&self, percent: f64) -> Option<i64> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = ((percent / 100.0 * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(upper(bucket).min(self.max?).max(self.min?));
+            }
+        }
+        self.max
+    }
+}
+
+impl LatencyTracker {
+    pub fn new() -> LatencyTracker {
+        LatencyTracker::default()
+    }
+
+    // add delay of every trade of update received at local time
+    pub fn record(&mut self, update: &BookUpdate, received: Timespec) {
+        for rec in update.records.iter() {
+            let trade = match rec {
+                RecordUpdate::Sell(trade) | RecordUpdate::Buy(trade) => trade,
+                _ => continue,
+            };
+            let delay = received - trade.precise_time();
+            self.by_book
+                .entry(update.book_id)
+                .or_default()
+                .add(delay.num_milliseconds());
+        }
+    }
+
+    pub fn histogram(&self, book_id: u16) -> Option<&Histogram> {
+        self.by_book.get(&book_id)
+    }
+
+    // books with at least one trade recorded
+    pub fn books(&self) -> Vec<u16> {
+        let mut books: Vec<u16> = self.by_book.keys().cloned().collect();
+        books.sort_unstable();
+        books
+    }
+}
+
+fn bucket(delay_ms: i64) -> usize {
+    if delay_ms < LINEAR_BUCKETS {
+        return delay_ms.max(0) as usize;
+    }
+    let ratio = delay_ms as f64 / LINEAR_BUCKETS as f64;
+    LINEAR_BUCKETS as usize + (ratio.ln() / BUCKET_GROWTH.ln()) as usize
+}
+
+// largest delay falling into bucket
+fn upper(bucket: usize) -> i64 {
+    if (bucket as i64) < LINEAR_BUCKETS {
+        return bucket as i64;
+    }
+    let power = (bucket as i64 - LINEAR_BUCKETS + 1) as i32;
+    (LINEAR_BUCKETS as f64 * BUCKET_GROWTH.powi(power)).ceil() as i64 - 1
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket, upper, Histogram, LatencyTracker};
+    use crate::data::messages::BookUpdate;
+    use std::str::FromStr;
+    use time::Timespec;
+
+    #[test]
+    fn buckets_cover_delay() {
+        for delay in (0..100_000).step_by(7) {
+            let bucket = bucket(delay);
+            assert!(delay <= upper(bucket), "{} above bucket {}", delay, bucket);
+            assert!(
+                bucket == 0 || delay > upper(bucket - 1),
+                "{} below bucket {}",
+                delay,
+                bucket
+            );
+        }
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(50.0), None);
+        for delay in 1..=100 {
+            histogram.add(delay);
+        }
+        histogram.add(-5);
+        assert_eq!(histogram.count(), 101);
+        assert_eq!(histogram.min(), Some(-5));
+        assert_eq!(histogram.percentile(0.0), Some(0));
+        assert_eq!(histogram.percentile(100.0), Some(100));
+        let median = histogram.percentile(50.0).unwrap();
+        assert!((50..=55).contains(&median), "median {}", median);
+        let p99 = histogram.percentile(99.0).unwrap();
+        assert!((99..=100).contains(&p99), "p99 {}", p99);
+    }
+
+    #[test]
+    fn tracker_per_book() {
+        let update = BookUpdate::from_str(r#"[189,4811424,[["o",1,"0.12906425","0.02691207"],["t","714116",0,"0.12906425","0.05946471",1504163848],["t","714117",1,"0.12906425","0.1",1504163847]]]"#).unwrap();
+        let mut tracker = LatencyTracker::new();
+        tracker.record(&update, Timespec::new(1504163848, 250_000_000));
+        assert_eq!(tracker.books(), vec![189]);
+        let histogram = tracker.histogram(189).unwrap();
+        assert_eq!(histogram.count(), 2);
+        assert_eq!(histogram.min(), Some(250));
+        assert_eq!(histogram.max(), Some(1250));
+    }
+}
@@ -0,0 +1,228 @@
+use super::json::Num;
+use super::pair::CurrencyPair;
+use crate::error::PoloError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// market metadata, id is the book_id of push api updates
+// ex: {"id":189,"pair":"BTC_BCH","baseId":28,"quoteId":292,"active":true,"frozen":false}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Market {
+    pub id: u16,
+    pub pair: CurrencyPair,
+    pub base_id: u16,
+    pub quote_id: u16,
+    // false if either currency is delisted or disabled
+    pub active: bool,
+    // trading is halted, book is still pushed
+    pub frozen: bool,
+}
+
+// markets by book_id and by pair, lets updates be routed before their initial book arrives
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarketRegistry {
+    markets: HashMap<u16, Market>,
+    by_pair: HashMap<CurrencyPair, u16>,
+}
+
+// returnTicker item, ex: "BTC_BCH": {"id":189,"last":"0.13161901",...,"isFrozen":"0"}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TickerItem {
+    id: Num<u16>,
+    is_frozen: Num<u64>,
+}
+
+// returnCurrencies item, ex: "BCH": {"id":292,"name":"Bitcoin Cash",...,"disabled":0,"delisted":0,"frozen":0}
+#[derive(Deserialize)]
+struct CurrencyItem {
+    id: Num<u16>,
+    #[serde(default = "enabled")]
+    disabled: Num<u64>,
+    #[serde(default = "enabled")]
+    delisted: Num<u64>,
+}
+
+fn enabled() -> Num<u64> {
+    Num(0)
+}
+
+impl MarketRegistry {
+    pub fn new() -> MarketRegistry {
+        MarketRegistry::default()
+    }
+
+    // replaces market with the same id or pair
+    pub fn insert(&mut self, market: Market) {
+        if let Some(id) = self.by_pair.remove(&market.pair) {
+            self.markets.remove(&id);
+        }
+        if let Some(old) = self.markets.remove(&market.id) {
+            self.by_pair.remove(&old.pair);
+        }
+        self.by_pair.insert(market.pair.clone(), market.id);
+        self.markets.insert(market.id, market);
+    }
+
+    pub fn get(&self, id: u16) -> Option<&Market> {
+        self.markets.get(&id)
+    }
+
+    pub fn by_pair(&self, pair: &CurrencyPair) -> Option<&Market> {
+        self.by_pair.get(pair).and_then(|id| self.markets.get(id))
+    }
+
+    pub fn pair(&self, id: u16) -> Option<CurrencyPair> {
+        self.get(id).map(|market| market.pair.clone())
+    }
+
+    // market of push channel given either as book_id, ex: "189", or as pair, ex: "BTC_BCH"
+    pub fn resolve(&self, channel: &str) -> Option<&Market> {
+        match channel.parse::<u16>() {
+            Ok(id) => self.get(id),
+            Err(_) => self.by_pair(&CurrencyPair::from_str(channel).ok()?),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.markets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty()
+    }
+
+    // markets in book_id order
+    pub fn markets(&self) -> Vec<&Market> {
+        let mut markets: Vec<&Market> = self.markets.values().collect();
+        markets.sort_by_key(|market| market.id);
+        markets
+    }
+
+    // registry from returnTicker and returnCurrencies public api responses
+    pub fn from_rest(ticker: &str, currencies: &str) -> Result<MarketRegistry, PoloError> {
+        let ticker: HashMap<CurrencyPair, TickerItem> = serde_json::from_str(ticker)?;
+        let currencies: HashMap<String, CurrencyItem> = serde_json::from_str(currencies)?;
+        let currency = |code: &str| {
+            currencies.get(code).ok_or_else(|| {
+                PoloError::wrong_data(format!("unknown currency {:?} in ticker", code))
+            })
+        };
+        let mut registry = MarketRegistry::new();
+        for (pair, item) in ticker {
+            let base = currency(&pair.base)?;
+            let quote = currency(&pair.quote)?;
+            let listed = |c: &CurrencyItem| c.disabled.0 == 0 && c.delisted.0 == 0;
+            registry.insert(Market {
+                id: item.id.0,
+                pair,
+                base_id: base.id.0,
+                quote_id: quote.id.0,
+                active: listed(base) && listed(quote),
+                frozen: item.is_frozen.0 != 0,
+            });
+        }
+        Ok(registry)
+    }
+
+    // registry saved with to_string
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<MarketRegistry, PoloError> {
+        MarketRegistry::from_str(&fs::read_to_string(path)?)
+    }
+}
+
+/**
+ * MarketRegistry conversion traits
+ * use:
+ *  let registry:: MarketRegistry = MarketRegistry::from_str(
+ *    r#"[{"id":189,"pair":"BTC_BCH","baseId":28,"quoteId":292,"active":true,"frozen":false}]"#
+ *  )
+ **/
+
+impl FromStr for MarketRegistry {
+    type Err = PoloError;
+    fn from_str(registry: &str) -> Result<Self, Self::Err> {
+        let markets: Vec<Market> = serde_json::from_str(registry)?;
+        let mut registry = MarketRegistry::new();
+        for market in markets {
+            registry.insert(market);
+        }
+        Ok(registry)
+    }
+}
+
+// json array of markets, MarketRegistry::from_str(&registry.to_string()) == Ok(registry)
+impl fmt::Display for MarketRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registry = serde_json::to_string(&self.markets()).map_err(|_| fmt::Error)?;
+        f.write_str(&registry)
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{Market, MarketRegistry};
+    use crate::data::pair::CurrencyPair;
+    use std::str::FromStr;
+
+    const TICKER: &str = r#"{
+        "BTC_BCH": {"id": 189, "last": "0.13161901", "lowestAsk": "0.13169621", "highestBid": "0.13161901", "isFrozen": "0"},
+        "USDT_XRP": {"id": 127, "last": "0.29", "isFrozen": "1"}
+    }"#;
+    const CURRENCIES: &str = r#"{
+        "BTC": {"id": 28, "name": "Bitcoin", "disabled": 0, "delisted": 0, "frozen": 0},
+        "BCH": {"id": 292, "name": "Bitcoin Cash", "disabled": 0, "delisted": 1, "frozen": 0},
+        "USDT": {"id": 214, "name": "Tether USD", "disabled": 0, "delisted": 0, "frozen": 0},
+        "XRP": {"id": 243, "name": "Ripple", "disabled": 0, "delisted": 0, "frozen": 0}
+    }"#;
+
+    #[test]
+    fn registry_from_rest() {
+        let registry = MarketRegistry::from_rest(TICKER, CURRENCIES).unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            registry.get(189),
+            Some(&Market {
+                id: 189,
+                pair: CurrencyPair::BtcBch,
+                base_id: 28,
+                quote_id: 292,
+                active: false,
+                frozen: false,
+            })
+        );
+        let xrp = registry.resolve("USDT_XRP").unwrap();
+        assert_eq!((xrp.id, xrp.active, xrp.frozen), (127, true, true));
+        assert_eq!(registry.resolve("127"), Some(xrp));
+        assert!(registry.resolve("1002").is_none());
+        assert!(MarketRegistry::from_rest(TICKER, r#"{"BTC": {"id": 28}}"#).is_err());
+    }
+
+    #[test]
+    fn registry_round_trip() {
+        let registry = MarketRegistry::from_rest(TICKER, CURRENCIES).unwrap();
+        let saved = registry.to_string();
+        assert!(saved.starts_with(r#"[{"id":127,"pair":"USDT_XRP","baseId":214"#));
+        assert_eq!(MarketRegistry::from_str(&saved).unwrap(), registry);
+    }
+
+    #[test]
+    fn registry_insert_replaces() {
+        let mut registry = MarketRegistry::from_rest(TICKER, CURRENCIES).unwrap();
+        let mut market = registry.get(189).unwrap().clone();
+        market.id = 190;
+        registry.insert(market);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.get(189).is_none());
+        assert_eq!(registry.pair(190), Some(CurrencyPair::BtcBch));
+    }
+}
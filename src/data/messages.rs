@@ -1,25 +1,48 @@
-// TODO: Convert to serde deserialization!
-use super::book::{Book, TradePairs};
-use super::json::Expect;
+use super::book::Book;
+use super::json::{Items, Num};
 use crate::error::PoloError;
-use json::{self, JsonValue};
+use json::JsonValue;
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
 use std::str::FromStr;
+use time::{self, Timespec};
 
 // ["t","714109",1,"0.12900000","1.03377186",1504163835]
-#[derive(Debug, Clone)]
+// newer api appends epoch_ms: ["t","714109",1,"0.12900000","1.03377186",1504163835,"1504163835148"]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TradeRecord {
+    // trade id, increasing per market
     pub id: u64,
-    pub tid: String,
     pub rate: String,
     pub amount: f64,
+    // time trade happened at exchange, seconds resolution
+    pub exchange_time: Timespec,
+    // the same time in milliseconds since epoch, if sent
+    pub epoch_ms: Option<u64>,
 }
 
 // ["o",1,"0.12774723","0.00000000"]
-#[derive(Debug, Clone)]
+// newer api appends epoch_ms: ["o",1,"0.12774723","0.00000000","1504163835148"]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BookRecord {
     pub rate: String,
     pub amount: f64,
+    // time order book changed at exchange in milliseconds since epoch, if sent
+    pub epoch_ms: Option<u64>,
+}
+
+// record which failed to parse in lenient mode, ex: type added to the protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownRecord {
+    // record json as received
+    pub raw: String,
+    pub error: String,
 }
 
 #[derive(Debug, Clone)]
@@ -29,11 +52,125 @@ pub enum RecordUpdate {
     Sell(TradeRecord),
     Buy(TradeRecord),
     Initial(Book),
+    // produced only by lenient parsing, see BookUpdate::from_str_lenient
+    Unknown(UnknownRecord),
+}
+
+// heartbeat channel, poloniex sends [1010] after a second without other messages
+pub const HEARTBEAT_CHANNEL: u16 = 1010;
+
+// subscription acknowledgement, ex: [1002,1] or [1002,0] for unsubscribe
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ack {
+    pub channel: u16,
+    pub subscribed: bool,
+}
+
+// any message received via push api, routed by channel id
+#[derive(Debug, Clone)]
+pub enum PushMessage {
+    Book(BookUpdate),
+    Ticker(TickerUpdate),
+    Volume24h(Volume24h),
+    Account(AccountUpdate),
+    Heartbeat,
+    Ack(Ack),
+    // server error reply, ex: {"error":"Invalid channel."}
+    Error(String),
+}
+
+// ticker channel, pushes updates of all markets
+pub const TICKER_CHANNEL: u16 = 1002;
+
+// ticker update message
+// ex: [1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickerUpdate {
+    pub pair_id: u16,
+    pub last: f64,
+    pub lowest_ask: f64,
+    pub highest_bid: f64,
+    pub percent_change: f64,
+    pub base_volume: f64,
+    pub quote_volume: f64,
+    pub is_frozen: bool,
+    pub high_24h: f64,
+    pub low_24h: f64,
+}
+
+// 24 hour exchange volume channel, pushed every minute
+pub const VOLUME_CHANNEL: u16 = 1003;
+
+// 24 hour exchange volume message, time is UTC with minute precision
+// ex: [1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409","ETH":"2645.921","USDT":"10832502.689","USDC":"1473758.501"}]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume24h {
+    pub time: Timespec,
+    pub users: u64,
+    // volume by base currency
+    pub volumes: HashMap<String, f64>,
+}
+
+// private account notifications channel, requires signed subscription
+pub const ACCOUNT_CHANNEL: u16 = 1000;
+
+// ["b",28,"e","-0.06500000"]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceRecord {
+    pub currency_id: u16,
+    pub wallet: String,
+    pub amount: f64,
+}
+
+// ["n",148,6083059,1,"0.03000000","2.00000000","2018-09-08 04:54:09"]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewOrderRecord {
+    pub pair_id: u16,
+    pub order_number: u64,
+    pub buy: bool,
+    pub rate: String,
+    pub amount: f64,
+    pub date: Timespec,
+}
+
+// ["o",6083059,"1.50000000"]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderUpdateRecord {
+    pub order_number: u64,
+    pub amount: f64,
+}
+
+// ["t",12345,"0.03000000","0.50000000","0.00250000",0,6083059,"0.00000375","2018-09-08 05:54:09"]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnTradeRecord {
+    pub trade_id: u64,
+    pub rate: String,
+    pub amount: f64,
+    pub fee_multiplier: f64,
+    pub funding_type: u64,
+    pub order_number: u64,
+    pub total_fee: f64,
+    pub date: Timespec,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountRecord {
+    Balance(BalanceRecord),
+    NewOrder(NewOrderRecord),
+    OrderUpdate(OrderUpdateRecord),
+    Trade(OwnTradeRecord),
+}
+
+// account notifications message
+// ex: [1000,"",[["b",28,"e","-0.06500000"],["o",6083059,"1.50000000"]]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountUpdate {
+    pub records: Vec<AccountRecord>,
 }
 
 // book update message
 // ex: [189,4811375,[["o",1,"0.12774723","0.00000000"],["t","714109",1,"0.12900000","1.03377186",1504163835]]]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BookUpdate {
     pub book_id: u16,
     pub record_id: u64,
@@ -43,146 +180,240 @@ pub struct BookUpdate {
 /**
  * TradeRecord conversion traits
  * use:
- *  let val = match serde_json::from_str(r#"["t","714109",1,"0.12900000","1.03377186",1504163835]"#) {
- *     serde_json::Array(vec) => vec,
- *     _ => Err(())
- *  };
- *  let records:: TradeRecord = TradeRecord::try_from(&val)
+ *  let record:: TradeRecord = serde_json::from_str(r#"["t","714109",1,"0.12900000","1.03377186",1504163835]"#)
  **/
 
+impl TradeRecord {
+    // exchange time with milliseconds resolution when epoch_ms is sent
+    pub fn precise_time(&self) -> Timespec {
+        self.epoch_ms.map_or(self.exchange_time, epoch_ms_time)
+    }
+
+    // items after "t" type, returns direction along with the record
+    fn read<'de, A: SeqAccess<'de>>(items: &mut Items<A>) -> Result<(u64, Self), A::Error> {
+        items.expect_len("trade record does not have 6 or 7 items");
+        let id: Num<u64> = items.next("trade record id")?;
+        let direction: Num<u64> = items.next("trade record direction")?;
+        let rate: String = items.next("trade record rate")?;
+        let amount: Num<f64> = items.next("trade record amount")?;
+        let time: Num<u64> = items.next("trade record time")?;
+        let epoch_ms: Option<Num<u64>> = items.try_next("trade record epoch_ms")?;
+        let record = Self {
+            id: id.0,
+            rate,
+            amount: amount.0,
+            exchange_time: Timespec::new(time.0 as i64, 0),
+            epoch_ms: epoch_ms.map(|ms| ms.0),
+        };
+        Ok((direction.0, record))
+    }
+}
+
+pub(crate) fn epoch_ms_time(ms: u64) -> Timespec {
+    Timespec::new((ms / 1000) as i64, (ms % 1000 * 1_000_000) as i32)
+}
+
+impl<'de> Deserialize<'de> for TradeRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TradeRecordVisitor;
+        impl<'de> Visitor<'de> for TradeRecordVisitor {
+            type Value = TradeRecord;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("trade record array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<TradeRecord, A::Error> {
+                let mut items = Items::new(seq, "trade record does not have 6 or 7 items");
+                items.next::<IgnoredAny>("trade record type")?;
+                let (_, record) = TradeRecord::read(&mut items)?;
+                items.end()?;
+                Ok(record)
+            }
+        }
+        deserializer.deserialize_seq(TradeRecordVisitor)
+    }
+}
+
 impl<'a> TryFrom<&'a JsonValue> for TradeRecord {
     type Error = PoloError;
     fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
-        if v.len() != 6 {
-            return Err(PoloError::wrong_data(format!(
-                "trade record does not have 6 items {:?}",
-                v
-            )));
-        }
-
-        let id: u64 = v[5].expect("trade record id")?;
-        let tid: String = v[1].expect("trade record tid")?;
-        let rate: String = v[3].expect("trade record rate")?;
-        let amount: f64 = v[4].expect("trade record amount")?;
-
-        Ok(Self {
-            id,
-            tid,
-            rate,
-            amount,
-        })
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
     }
 }
 
 /**
  * BookRecord conversion traits
  * use:
- *  let val = match json::parse(r#"["o",0,"0.12900000","1.03377186"]"#) {
- *     serde_json::Array(vec) => vec,
- *     _ => Err(())
- *  };
- *  let records:: BookRecord = BookRecord::try_from(&val)
+ *  let record:: BookRecord = serde_json::from_str(r#"["o",0,"0.12900000","1.03377186"]"#)
  **/
 
+impl BookRecord {
+    // items after "o" type, returns direction along with the record
+    fn read<'de, A: SeqAccess<'de>>(items: &mut Items<A>) -> Result<(u64, Self), A::Error> {
+        items.expect_len("book record does not have 4 or 5 items");
+        let direction: Num<u64> = items.next("book record direction")?;
+        let rate: String = items.next("book record rate")?;
+        let amount: Num<f64> = items.next("book record amount")?;
+        let epoch_ms: Option<Num<u64>> = items.try_next("book record epoch_ms")?;
+        let record = Self {
+            rate,
+            amount: amount.0,
+            epoch_ms: epoch_ms.map(|ms| ms.0),
+        };
+        Ok((direction.0, record))
+    }
+}
+
+impl<'de> Deserialize<'de> for BookRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BookRecordVisitor;
+        impl<'de> Visitor<'de> for BookRecordVisitor {
+            type Value = BookRecord;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("book record array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<BookRecord, A::Error> {
+                let mut items = Items::new(seq, "book record does not have 4 or 5 items");
+                items.next::<IgnoredAny>("book record type")?;
+                let (_, record) = BookRecord::read(&mut items)?;
+                items.end()?;
+                Ok(record)
+            }
+        }
+        deserializer.deserialize_seq(BookRecordVisitor)
+    }
+}
+
 impl<'a> TryFrom<&'a JsonValue> for BookRecord {
     type Error = PoloError;
     fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
-        if v.len() != 4 {
-            return Err(PoloError::wrong_data(format!(
-                "book record does not have 4 items {:?}",
-                v
-            )));
-        }
-
-        let rate: String = v[2].expect("book record rate")?;
-        let amount: f64 = v[3].expect("book record amount")?;
-
-        Ok(Self { rate, amount })
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
     }
 }
 
 /**
  * RecordUpdate enum conversion traits
  * use:
- *  let val = match json::parse(r#"["o",0,"0.12900000","1.03377186"]"#);
- *  let records:: RecordUpdate = RecordUpdate::try_from(&val)
+ *  let record:: RecordUpdate = serde_json::from_str(r#"["o",0,"0.12900000","1.03377186"]"#)
  **/
 
-impl<'a> TryFrom<&'a JsonValue> for RecordUpdate {
-    type Error = PoloError;
+impl<'de> Deserialize<'de> for RecordUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RecordUpdateVisitor;
+        impl<'de> Visitor<'de> for RecordUpdateVisitor {
+            type Value = RecordUpdate;
 
-    fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
-        let err = |msg| {
-            Err(PoloError::wrong_data(format!(
-                "book update record {} {:?}",
-                msg, v
-            )))
-        };
-        if v.len() < 2 {
-            return err("has less than 2 items");
-        }
-
-        match v[0].as_str() {
-            Some("o") => {
-                let direction: u64 = v[1].expect("book record direction")?;
-                let record = BookRecord::try_from(v)?;
-                match direction {
-                    0 => Ok(RecordUpdate::SellTotal(record)),
-                    1 => Ok(RecordUpdate::BuyTotal(record)),
-                    _ => err("has unknown dir"),
-                }
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("book update record array")
             }
-            Some("t") => {
-                let direction: u64 = v[2].expect("book record direction")?;
-                let record = TradeRecord::try_from(v)?;
-                match direction {
-                    0 => Ok(RecordUpdate::Sell(record)),
-                    1 => Ok(RecordUpdate::Buy(record)),
-                    _ => err("has unknown dir"),
-                }
-            }
-            Some("i") => {
-                let book = Book::try_from(&v[1])?;
-                Ok(RecordUpdate::Initial(book))
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<RecordUpdate, A::Error> {
+                let err = |msg| Err(de::Error::custom(format!("book update record {}", msg)));
+                let mut items = Items::new(seq, "book update record has less than 2 items");
+                let kind: String = items.next("book update record type")?;
+                let record = match kind.as_str() {
+                    "o" => match BookRecord::read(&mut items)? {
+                        (0, record) => RecordUpdate::SellTotal(record),
+                        (1, record) => RecordUpdate::BuyTotal(record),
+                        _ => return err("has unknown dir"),
+                    },
+                    "t" => match TradeRecord::read(&mut items)? {
+                        (0, record) => RecordUpdate::Sell(record),
+                        (1, record) => RecordUpdate::Buy(record),
+                        _ => return err("has unknown dir"),
+                    },
+                    "i" => {
+                        let book: Book = items.next("initial book")?;
+                        items.skip()?;
+                        return Ok(RecordUpdate::Initial(book));
+                    }
+                    _ => return err("has unknown type"),
+                };
+                items.end()?;
+                Ok(record)
             }
-            _ => err("has unknown type"),
         }
+        deserializer.deserialize_seq(RecordUpdateVisitor)
     }
 }
 
-impl<'a> TryFrom<&'a JsonValue> for TradePairs {
+impl<'a> TryFrom<&'a JsonValue> for RecordUpdate {
     type Error = PoloError;
     fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
-        let err = |msg| Err(PoloError::wrong_data(format!("{} {:?}", msg, v)));
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
+    }
+}
 
-        if !v.is_string() {
-            return err("book's trade pairs is not string");
-        };
-        match v.as_str() {
-            Some("BTC_BCH") => Ok(TradePairs::BtcBch),
-            Some("BTC_ETH") => Ok(TradePairs::BtcEth),
-            Some("BTC_LTC") => Ok(TradePairs::BtcLtc),
-            Some("BTC_ZEC") => Ok(TradePairs::BtcZec),
-            Some("USDT_BTC") => Ok(TradePairs::UsdtBtc),
-            Some("USDT_ETH") => Ok(TradePairs::UsdtEth),
-            Some("USDT_LTC") => Ok(TradePairs::UsdtLtc),
-            Some("USDT_BCH") => Ok(TradePairs::UsdtBch),
-            Some("USDT_ZEC") => Ok(TradePairs::UsdtZec),
-            Some("USDT_XRP") => Ok(TradePairs::UsdtXrp),
-            _ => err("unknown trade pair"),
-        }
-    }
-}
-
-/*
-impl<'a> TryFrom<(&'a str, &'a JsonValue)> for Record {
-  type Error = PoloError;
-  fn try_from((srate, vamount): (&'a str, &'a JsonValue)) -> Result<Self, Self::Error> {
-    let rate: String = srate.to_owned();
-    let amount: f64 = vamount.expect("record amount")?;
-    Ok(Self::new(rate, amount))
-  }
-}*/
+// initial books are equal by their orders, the rest is local state not sent over the wire
+impl PartialEq for RecordUpdate {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RecordUpdate::SellTotal(a), RecordUpdate::SellTotal(b)) => a == b,
+            (RecordUpdate::BuyTotal(a), RecordUpdate::BuyTotal(b)) => a == b,
+            (RecordUpdate::Sell(a), RecordUpdate::Sell(b)) => a == b,
+            (RecordUpdate::Buy(a), RecordUpdate::Buy(b)) => a == b,
+            (RecordUpdate::Initial(a), RecordUpdate::Initial(b)) => {
+                (&a.pair, &a.sell, &a.buy) == (&b.pair, &b.sell, &b.buy)
+            }
+            (RecordUpdate::Unknown(a), RecordUpdate::Unknown(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// push api format, serde_json::from_str reads the same value back
+impl Serialize for RecordUpdate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RecordUpdate::SellTotal(rec) => book_wire(0, rec, serializer),
+            RecordUpdate::BuyTotal(rec) => book_wire(1, rec, serializer),
+            RecordUpdate::Sell(rec) => trade_wire(0, rec, serializer),
+            RecordUpdate::Buy(rec) => trade_wire(1, rec, serializer),
+            RecordUpdate::Initial(book) => ("i", book).serialize(serializer),
+            RecordUpdate::Unknown(rec) => RawValue::from_string(rec.raw.clone())
+                .map_err(ser::Error::custom)?
+                .serialize(serializer),
+        }
+    }
+}
+
+// ["o",1,"0.12774723","0.00000000"], epoch_ms is appended if known
+fn book_wire<S: Serializer>(dir: u8, rec: &BookRecord, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(None)?;
+    seq.serialize_element("o")?;
+    seq.serialize_element(&dir)?;
+    seq.serialize_element(&rec.rate)?;
+    seq.serialize_element(&Num(rec.amount))?;
+    if let Some(ms) = rec.epoch_ms {
+        seq.serialize_element(&ms.to_string())?;
+    }
+    seq.end()
+}
+
+// ["t","714109",1,"0.12900000","1.03377186",1504163835], epoch_ms is appended if known
+fn trade_wire<S: Serializer>(dir: u8, rec: &TradeRecord, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(None)?;
+    seq.serialize_element("t")?;
+    seq.serialize_element(&rec.id.to_string())?;
+    seq.serialize_element(&dir)?;
+    seq.serialize_element(&rec.rate)?;
+    seq.serialize_element(&Num(rec.amount))?;
+    seq.serialize_element(&rec.exchange_time.sec)?;
+    if let Some(ms) = rec.epoch_ms {
+        seq.serialize_element(&ms.to_string())?;
+    }
+    seq.end()
+}
+
+// cheap check for heartbeat message without parsing json
+pub fn is_heartbeat(msg: &str) -> bool {
+    msg.chars()
+        .filter(|c| !c.is_whitespace())
+        .eq("[1010]".chars())
+}
 
 /**
  * BookUpdate conversion traits
@@ -195,43 +426,581 @@ impl<'a> TryFrom<(&'a str, &'a JsonValue)> for Record {
 impl FromStr for BookUpdate {
     type Err = PoloError;
     fn from_str(order: &str) -> Result<Self, Self::Err> {
-        let v = json::parse(order)?;
-        BookUpdate::try_from(v)
+        serde_json::from_str(order).map_err(PoloError::from)
+    }
+}
+
+// push api frame, BookUpdate::from_str(&update.to_string()) == Ok(update)
+impl fmt::Display for BookUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frame = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&frame)
+    }
+}
+
+impl Serialize for BookUpdate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.book_id, self.record_id, &self.records).serialize(serializer)
+    }
+}
+
+impl BookUpdate {
+    // records which fail to parse are kept as RecordUpdate::Unknown instead of failing the update
+    pub fn from_str_lenient(order: &str) -> Result<Self, PoloError> {
+        let mut deserializer = serde_json::Deserializer::from_str(order);
+        let update = deserializer.deserialize_seq(BookUpdateVisitor { lenient: true })?;
+        deserializer.end()?;
+        Ok(update)
+    }
+}
+
+impl<'de> Deserialize<'de> for BookUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(BookUpdateVisitor { lenient: false })
     }
 }
 
 impl TryFrom<JsonValue> for BookUpdate {
     type Error = PoloError;
     fn try_from(v: JsonValue) -> Result<Self, Self::Error> {
-        let err = |msg| Err(PoloError::wrong_data(format!("{} {:?}", msg, v)));
+        BookUpdate::from_str(&v.dump())
+    }
+}
 
-        if v.len() != 3 {
-            return err("book update is not triple");
-        }
-        if !v[2].is_array() {
-            return err("book update records: expected array got");
+struct BookUpdateVisitor {
+    lenient: bool,
+}
+
+impl<'de> Visitor<'de> for BookUpdateVisitor {
+    type Value = BookUpdate;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("book update array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<BookUpdate, A::Error> {
+        let mut items = Items::new(seq, "book update is not triple");
+        let book_id: Num<u16> = items.next("book update book_id")?;
+        let record_id: Num<u64> = items.next("book update record_id")?;
+        let records = read_records(&mut items, self.lenient)?
+            .ok_or_else(|| de::Error::custom("book update is not triple"))?;
+        items.end()?;
+        Ok(BookUpdate {
+            book_id: book_id.0,
+            record_id: record_id.0,
+            records,
+        })
+    }
+}
+
+// record which is kept as RecordUpdate::Unknown if it does not parse
+struct LenientRecord(RecordUpdate);
+
+impl<'de> Deserialize<'de> for LenientRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = <&RawValue>::deserialize(deserializer)?;
+        let record = serde_json::from_str(raw.get()).unwrap_or_else(|err: serde_json::Error| {
+            RecordUpdate::Unknown(UnknownRecord {
+                raw: raw.get().to_owned(),
+                error: err.to_string(),
+            })
+        });
+        Ok(LenientRecord(record))
+    }
+}
+
+// records array of book update, None if update ends before it
+fn read_records<'de, A: SeqAccess<'de>>(
+    items: &mut Items<A>,
+    lenient: bool,
+) -> Result<Option<Vec<RecordUpdate>>, A::Error> {
+    if !lenient {
+        return items.try_next("book update records");
+    }
+    let records: Option<Vec<LenientRecord>> = items.try_next("book update records")?;
+    Ok(records.map(|records| records.into_iter().map(|rec| rec.0).collect()))
+}
+
+// payload of ticker, volume and account messages: [channel, null, payload]
+trait ChannelPayload: Sized {
+    const CHANNEL: u16;
+    // message name used in errors, ex: "ticker update"
+    const NAME: &'static str;
+    fn read<'de, A: SeqAccess<'de>>(seq: A) -> Result<Self, A::Error>;
+}
+
+// payload array read by ChannelPayload::read
+struct Payload<T>(T);
+
+impl<'de, T: ChannelPayload> Deserialize<'de> for Payload<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PayloadVisitor<T>(PhantomData<T>);
+        impl<'de, T: ChannelPayload> Visitor<'de> for PayloadVisitor<T> {
+            type Value = Payload<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{} array", T::NAME)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Payload<T>, A::Error> {
+                T::read(seq).map(Payload)
+            }
         }
+        deserializer.deserialize_seq(PayloadVisitor(PhantomData))
+    }
+}
 
-        let mut records: Vec<RecordUpdate> = vec![];
-        for record in v[2].members() {
-            records.push(RecordUpdate::try_from(record)?)
+// whole [channel, null, payload] message
+struct ChannelMessageVisitor<T>(PhantomData<T>);
+
+impl<'de, T: ChannelPayload> Visitor<'de> for ChannelMessageVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} message array", T::NAME)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<T, A::Error> {
+        let mut items = Items::new(seq, "channel message is not triple");
+        let channel: Num<u16> = items.next("channel message channel")?;
+        if channel.0 != T::CHANNEL {
+            return Err(de::Error::custom(format!(
+                "{} has wrong channel {}",
+                T::NAME,
+                channel.0
+            )));
         }
+        items.next::<IgnoredAny>("channel message status")?;
+        let payload: Payload<T> = items.next(T::NAME)?;
+        items.end()?;
+        Ok(payload.0)
+    }
+}
+
+// parse UTC date item with given strptime format
+fn read_time<'de, A: SeqAccess<'de>>(
+    items: &mut Items<A>,
+    format: &str,
+    msg: &str,
+) -> Result<Timespec, A::Error> {
+    let date: String = items.next(msg)?;
+    time::strptime(&date, format)
+        .map(|tm| tm.to_timespec())
+        .map_err(|err| de::Error::custom(format!("{}: {} {:?}", msg, err, date)))
+}
 
+/**
+ * TickerUpdate conversion traits
+ * use:
+ *  let ticker:: TickerUpdate = TickerUpdate::from_str(
+ *    r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#
+ *  )
+ **/
+
+impl ChannelPayload for TickerUpdate {
+    const CHANNEL: u16 = TICKER_CHANNEL;
+    const NAME: &'static str = "ticker update";
+
+    fn read<'de, A: SeqAccess<'de>>(seq: A) -> Result<Self, A::Error> {
+        let mut items = Items::new(seq, "ticker update does not have 10 items");
+        let pair_id: Num<u16> = items.next("ticker currencyPairId")?;
+        let last: Num<f64> = items.next("ticker last")?;
+        let lowest_ask: Num<f64> = items.next("ticker lowestAsk")?;
+        let highest_bid: Num<f64> = items.next("ticker highestBid")?;
+        let percent_change: Num<f64> = items.next("ticker percentChange")?;
+        let base_volume: Num<f64> = items.next("ticker baseVolume")?;
+        let quote_volume: Num<f64> = items.next("ticker quoteVolume")?;
+        let is_frozen: Num<u64> = items.next("ticker isFrozen")?;
+        let high_24h: Num<f64> = items.next("ticker high24hr")?;
+        let low_24h: Num<f64> = items.next("ticker low24hr")?;
+        // newer api versions append fields, first 10 are stable
+        items.skip()?;
         Ok(Self {
-            book_id: v[0].expect("book update book_id")?,
-            record_id: v[1].expect("book update record_id")?,
-            records,
+            pair_id: pair_id.0,
+            last: last.0,
+            lowest_ask: lowest_ask.0,
+            highest_bid: highest_bid.0,
+            percent_change: percent_change.0,
+            base_volume: base_volume.0,
+            quote_volume: quote_volume.0,
+            is_frozen: is_frozen.0 != 0,
+            high_24h: high_24h.0,
+            low_24h: low_24h.0,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for TickerUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ChannelMessageVisitor(PhantomData))
+    }
+}
+
+impl FromStr for TickerUpdate {
+    type Err = PoloError;
+    fn from_str(msg: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(msg).map_err(PoloError::from)
+    }
+}
+
+impl TryFrom<JsonValue> for TickerUpdate {
+    type Error = PoloError;
+    fn try_from(v: JsonValue) -> Result<Self, Self::Error> {
+        TickerUpdate::from_str(&v.dump())
+    }
+}
+
+/**
+ * Volume24h conversion traits
+ * use:
+ *  let volume:: Volume24h = Volume24h::from_str(
+ *    r#"[1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409","ETH":"2645.921"}]]"#
+ *  )
+ **/
+
+impl ChannelPayload for Volume24h {
+    const CHANNEL: u16 = VOLUME_CHANNEL;
+    const NAME: &'static str = "volume update";
+
+    fn read<'de, A: SeqAccess<'de>>(seq: A) -> Result<Self, A::Error> {
+        let mut items = Items::new(seq, "volume update does not have 3 items");
+        let time = read_time(&mut items, "%Y-%m-%d %H:%M", "volume update time")?;
+        let users: Num<u64> = items.next("volume update users")?;
+        let volumes: HashMap<String, Num<f64>> = items.next("volume update volumes")?;
+        items.end()?;
+        Ok(Self {
+            time,
+            users: users.0,
+            volumes: volumes
+                .into_iter()
+                .map(|(currency, amount)| (currency, amount.0))
+                .collect(),
         })
     }
 }
 
+impl<'de> Deserialize<'de> for Volume24h {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ChannelMessageVisitor(PhantomData))
+    }
+}
+
+impl FromStr for Volume24h {
+    type Err = PoloError;
+    fn from_str(msg: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(msg).map_err(PoloError::from)
+    }
+}
+
+impl TryFrom<JsonValue> for Volume24h {
+    type Error = PoloError;
+    fn try_from(v: JsonValue) -> Result<Self, Self::Error> {
+        Volume24h::from_str(&v.dump())
+    }
+}
+
+/**
+ * AccountRecord enum conversion traits
+ * use:
+ *  let record:: AccountRecord = serde_json::from_str(r#"["b",28,"e","-0.06500000"]"#)
+ **/
+
+impl<'de> Deserialize<'de> for AccountRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AccountRecordVisitor;
+        impl<'de> Visitor<'de> for AccountRecordVisitor {
+            type Value = AccountRecord;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("account record array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<AccountRecord, A::Error> {
+                let mut items = Items::new(seq, "account record does not have enough items");
+                let kind: String = items.next("account record type")?;
+                let record = match kind.as_str() {
+                    "b" => {
+                        let currency_id: Num<u16> = items.next("balance record currency id")?;
+                        let wallet: String = items.next("balance record wallet")?;
+                        let amount: Num<f64> = items.next("balance record amount")?;
+                        AccountRecord::Balance(BalanceRecord {
+                            currency_id: currency_id.0,
+                            wallet,
+                            amount: amount.0,
+                        })
+                    }
+                    "n" => {
+                        let pair_id: Num<u16> = items.next("new order record pair id")?;
+                        let order_number: Num<u64> = items.next("new order record number")?;
+                        let order_type: Num<u64> = items.next("new order record type")?;
+                        let rate: String = items.next("new order record rate")?;
+                        let amount: Num<f64> = items.next("new order record amount")?;
+                        let date =
+                            read_time(&mut items, "%Y-%m-%d %H:%M:%S", "new order record date")?;
+                        AccountRecord::NewOrder(NewOrderRecord {
+                            pair_id: pair_id.0,
+                            order_number: order_number.0,
+                            buy: order_type.0 == 1,
+                            rate,
+                            amount: amount.0,
+                            date,
+                        })
+                    }
+                    "o" => {
+                        let order_number: Num<u64> = items.next("order update record number")?;
+                        let amount: Num<f64> = items.next("order update record amount")?;
+                        AccountRecord::OrderUpdate(OrderUpdateRecord {
+                            order_number: order_number.0,
+                            amount: amount.0,
+                        })
+                    }
+                    "t" => {
+                        let trade_id: Num<u64> = items.next("trade record id")?;
+                        let rate: String = items.next("trade record rate")?;
+                        let amount: Num<f64> = items.next("trade record amount")?;
+                        let fee_multiplier: Num<f64> = items.next("trade record fee multiplier")?;
+                        let funding_type: Num<u64> = items.next("trade record funding type")?;
+                        let order_number: Num<u64> = items.next("trade record order number")?;
+                        let total_fee: Num<f64> = items.next("trade record total fee")?;
+                        let date = read_time(&mut items, "%Y-%m-%d %H:%M:%S", "trade record date")?;
+                        AccountRecord::Trade(OwnTradeRecord {
+                            trade_id: trade_id.0,
+                            rate,
+                            amount: amount.0,
+                            fee_multiplier: fee_multiplier.0,
+                            funding_type: funding_type.0,
+                            order_number: order_number.0,
+                            total_fee: total_fee.0,
+                            date,
+                        })
+                    }
+                    _ => {
+                        return Err(de::Error::custom(format!(
+                            "account record has unknown type {:?}",
+                            kind
+                        )))
+                    }
+                };
+                // newer api versions append fields, only leading ones are parsed
+                items.skip()?;
+                Ok(record)
+            }
+        }
+        deserializer.deserialize_seq(AccountRecordVisitor)
+    }
+}
+
+impl<'a> TryFrom<&'a JsonValue> for AccountRecord {
+    type Error = PoloError;
+    fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
+    }
+}
+
+/**
+ * AccountUpdate conversion traits
+ * use:
+ *  let update:: AccountUpdate = AccountUpdate::from_str(
+ *    r#"[1000,"",[["b",28,"e","-0.06500000"],["o",6083059,"1.50000000"]]]"#
+ *  )
+ **/
+
+impl ChannelPayload for AccountUpdate {
+    const CHANNEL: u16 = ACCOUNT_CHANNEL;
+    const NAME: &'static str = "account update";
+
+    fn read<'de, A: SeqAccess<'de>>(mut seq: A) -> Result<Self, A::Error> {
+        let mut records = vec![];
+        while let Some(record) = seq.next_element()? {
+            records.push(record);
+        }
+        Ok(Self { records })
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountUpdate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ChannelMessageVisitor(PhantomData))
+    }
+}
+
+impl FromStr for AccountUpdate {
+    type Err = PoloError;
+    fn from_str(msg: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(msg).map_err(PoloError::from)
+    }
+}
+
+impl TryFrom<JsonValue> for AccountUpdate {
+    type Error = PoloError;
+    fn try_from(v: JsonValue) -> Result<Self, Self::Error> {
+        AccountUpdate::from_str(&v.dump())
+    }
+}
+
+/**
+ * PushMessage conversion traits
+ * use:
+ *  let msg:: PushMessage = PushMessage::from_str(r#"[1010]"#)
+ **/
+
+impl FromStr for PushMessage {
+    type Err = PoloError;
+    fn from_str(msg: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(msg).map_err(PoloError::from)
+    }
+}
+
+impl PushMessage {
+    // book update records are parsed as BookUpdate::from_str_lenient does
+    pub fn from_str_lenient(msg: &str) -> Result<Self, PoloError> {
+        let mut deserializer = serde_json::Deserializer::from_str(msg);
+        let msg = deserializer.deserialize_any(PushMessageVisitor { lenient: true })?;
+        deserializer.end()?;
+        Ok(msg)
+    }
+}
+
+impl<'de> Deserialize<'de> for PushMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PushMessageVisitor { lenient: false })
+    }
+}
+
+struct PushMessageVisitor {
+    lenient: bool,
+}
+
+impl<'de> Visitor<'de> for PushMessageVisitor {
+    type Value = PushMessage;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("push message array or error object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<PushMessage, A::Error> {
+        let mut error = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "error" {
+                error = Some(map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        error
+            .map(PushMessage::Error)
+            .ok_or_else(|| de::Error::custom("push message object has no error"))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<PushMessage, A::Error> {
+        let err = |msg| Err(de::Error::custom(msg));
+        let mut items = Items::new(seq, "book update is not triple");
+        let channel: Num<u16> = items.next("push message channel")?;
+        let second: Option<Status> = items.try_next("ack status")?;
+        let status = match second {
+            Some(Status(Some(status))) => status,
+            Some(Status(None)) => return read_channel_update(channel.0, items),
+            None if channel.0 == HEARTBEAT_CHANNEL => return Ok(PushMessage::Heartbeat),
+            None => return err("push message has unknown channel"),
+        };
+        let records = match read_records(&mut items, self.lenient)? {
+            Some(records) => records,
+            None => {
+                return match status {
+                    0 | 1 => Ok(PushMessage::Ack(Ack {
+                        channel: channel.0,
+                        subscribed: status == 1,
+                    })),
+                    _ => err("ack has unknown status"),
+                }
+            }
+        };
+        items.end()?;
+        Ok(PushMessage::Book(BookUpdate {
+            book_id: channel.0,
+            record_id: status,
+            records,
+        }))
+    }
+}
+
+// second item of push message: record id or ack status,
+// null or "" for ticker, volume and account updates
+struct Status(Option<u64>);
+
+impl<'de> Deserialize<'de> for Status {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StatusVisitor;
+        impl<'de> Visitor<'de> for StatusVisitor {
+            type Value = Status;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("u64 as number or string, null or empty string")
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Status, E> {
+                Ok(Status(None))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Status, E> {
+                if v.is_empty() {
+                    return Ok(Status(None));
+                }
+                self.visit_u64(
+                    v.parse()
+                        .map_err(|_| E::custom(format!("expected u64 got {:?}", v)))?,
+                )
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Status, E> {
+                Ok(Status(Some(v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Status, E> {
+                u64::try_from(v)
+                    .map(|v| Status(Some(v)))
+                    .map_err(|_| E::custom(format!("expected u64 got {}", v)))
+            }
+        }
+        deserializer.deserialize_any(StatusVisitor)
+    }
+}
+
+// data item of ticker, volume or account update parsed by its json conversion
+fn read_channel_update<'de, A: SeqAccess<'de>>(
+    channel: u16,
+    mut items: Items<A>,
+) -> Result<PushMessage, A::Error> {
+    let msg = match channel {
+        TICKER_CHANNEL => {
+            let ticker: Payload<TickerUpdate> = items.next(TickerUpdate::NAME)?;
+            PushMessage::Ticker(ticker.0)
+        }
+        VOLUME_CHANNEL => {
+            let volume: Payload<Volume24h> = items.next(Volume24h::NAME)?;
+            PushMessage::Volume24h(volume.0)
+        }
+        ACCOUNT_CHANNEL => {
+            let account: Payload<AccountUpdate> = items.next(AccountUpdate::NAME)?;
+            PushMessage::Account(account.0)
+        }
+        _ => return Err(de::Error::custom("push message has unknown channel")),
+    };
+    items.end()?;
+    Ok(msg)
+}
+
 /**
  ** TESTS TESTS TESTS
  **/
 
 #[cfg(test)]
 mod tests {
-    use super::BookUpdate;
+    use super::{
+        is_heartbeat, AccountRecord, AccountUpdate, Ack, BookRecord, BookUpdate, PushMessage,
+        RecordUpdate, TickerUpdate, TradeRecord, UnknownRecord, Volume24h,
+    };
+    use std::convert::TryFrom;
     use std::str::FromStr;
 
     #[test]
@@ -240,6 +1009,16 @@ mod tests {
         if let Err(error) = BookUpdate::from_str(order) {
             panic!("failed to process json {}", error);
         }
+        let v = json::parse(order).unwrap();
+        assert_eq!(
+            RecordUpdate::try_from(&v[2][0]).unwrap(),
+            RecordUpdate::BuyTotal(BookRecord::try_from(&v[2][0]).unwrap())
+        );
+        assert_eq!(TradeRecord::try_from(&v[2][1]).unwrap().rate, "0.12906425");
+        assert_eq!(
+            BookUpdate::try_from(v).unwrap(),
+            BookUpdate::from_str(order).unwrap()
+        );
     }
 
     #[test]
@@ -281,4 +1060,276 @@ mod tests {
             panic!("failed to process json {:?}", error);
         }
     }
+
+    #[test]
+    fn json_deserialize_records() {
+        let trade: TradeRecord =
+            serde_json::from_str(r#"["t","714109",1,"0.12900000","1.03377186",1504163835]"#)
+                .unwrap();
+        assert_eq!(
+            (trade.id, trade.exchange_time.sec),
+            (714_109, 1_504_163_835)
+        );
+        assert_eq!(trade.amount, 1.033_771_86);
+        assert_eq!(trade.precise_time(), trade.exchange_time);
+        let trade: TradeRecord = serde_json::from_str(
+            r#"["t","714109",1,"0.12900000","1.03377186",1504163835,"1504163835148"]"#,
+        )
+        .unwrap();
+        assert_eq!(trade.epoch_ms, Some(1_504_163_835_148));
+        assert_eq!(trade.precise_time().nsec, 148_000_000);
+        let book: BookRecord = serde_json::from_str(r#"["o",0,"0.12900000",1.5]"#).unwrap();
+        assert_eq!((book.rate.as_str(), book.amount), ("0.12900000", 1.5));
+        let book: BookRecord =
+            serde_json::from_str(r#"["o",0,"0.12900000",1.5,1504163835148]"#).unwrap();
+        assert_eq!(book.epoch_ms, Some(1_504_163_835_148));
+        match serde_json::from_str(r#"["o",0,"0.12900000","1.03377186"]"#) {
+            Ok(RecordUpdate::SellTotal(record)) => assert_eq!(record.amount, 1.033_771_86),
+            rec => panic!("expected sell total {:?}", rec),
+        }
+    }
+
+    #[test]
+    fn json_serialize_round_trip() {
+        let frames = [
+            r#"[189,4811424,[["o",1,"0.12906425","0.02691207"],["t","714116",0,"0.12906425","0.05946471",1504163848]]]"#,
+            r#"[189,4811424,[["o",1,"0.12906425","0.02691207","1504163848123"],["t","714116",0,"0.12906425","0.05946471",1504163848,"1504163848123"]]]"#,
+            r#"[189,5130995,[["i",{"currencyPair":"BTC_BCH","orderBook":[{"0.13161901":"0.23709568"},{"0.13169621":"0.23310000"}]}]]]"#,
+            r#"[189,5130995,[["i",{"currencyPair":"BTC_BCH","orderBook":[{"0.13161901":"0.23709568","0.13164313":"0.17328089","0.2":"1.00000000"},{"0.13169621":"0.23310000","0.1316":"0.50000000","0.12":"2.00000000"}]}]]]"#,
+        ];
+        for frame in frames.iter() {
+            let update = BookUpdate::from_str(frame).unwrap();
+            assert_eq!(update.to_string(), *frame);
+            assert_eq!(BookUpdate::from_str(&update.to_string()).unwrap(), update);
+        }
+        let update = BookUpdate {
+            book_id: 7,
+            record_id: 1,
+            records: vec![RecordUpdate::SellTotal(BookRecord {
+                rate: "0.1".to_owned(),
+                amount: 1.0 / 3.0,
+                epoch_ms: None,
+            })],
+        };
+        assert_eq!(BookUpdate::from_str(&update.to_string()).unwrap(), update);
+        let shuffled = r#"[189,5130995,[["i",{"currencyPair":"BTC_BCH","orderBook":[{"0.2":"1","0.13164313":"0.17328089","0.13161901":"0.23709568"},{"0.12":"2","0.13169621":"0.2331","0.1316":"0.5"}]}]]]"#;
+        assert_eq!(
+            BookUpdate::from_str(shuffled).unwrap().to_string(),
+            frames[3]
+        );
+    }
+
+    #[test]
+    fn json_deserialize_lenient() {
+        let order = r#"[189,4811424,[["o",1,"0.12906425","0.02691207"],["x",7,{"new":true}],["o",3,"0.1","0.2"]]]"#;
+        assert!(BookUpdate::from_str(order).is_err());
+        let update = BookUpdate::from_str_lenient(order).unwrap();
+        assert_eq!(update.records.len(), 3);
+        assert!(matches!(update.records[0], RecordUpdate::BuyTotal(_)));
+        match update.records[1] {
+            RecordUpdate::Unknown(UnknownRecord { ref raw, ref error }) => {
+                assert_eq!(raw, r#"["x",7,{"new":true}]"#);
+                assert!(error.contains("has unknown type"));
+            }
+            ref rec => panic!("expected unknown record {:?}", rec),
+        }
+        assert!(matches!(update.records[2], RecordUpdate::Unknown(_)));
+        assert_eq!(update.to_string(), order);
+        match PushMessage::from_str_lenient(order) {
+            Ok(PushMessage::Book(book)) => assert_eq!(book, update),
+            msg => panic!("expected book update {:?}", msg),
+        }
+        assert!(BookUpdate::from_str_lenient("[189,4811424,{}]").is_err());
+    }
+
+    #[test]
+    fn json_deserialize_errors() {
+        let err = |msg| BookUpdate::from_str(msg).unwrap_err().to_string();
+        assert!(err(r#"[189,4811424]"#).contains("book update is not triple"));
+        assert!(err(r#"[189,4811424,[["o",3,"0.1","0.2"]]]"#)
+            .contains("book update record has unknown dir"));
+        assert!(err(r#"[189,4811424,[["f",1,"0.1","0.2"]]]"#)
+            .contains("book update record has unknown type"));
+        assert!(err(r#"[189,4811424,[["o",1,"0.1"]]]"#)
+            .contains("book record does not have 4 or 5 items"));
+        assert!(err(r#"[189,4811424,[["t","714116",0,"0.1","0.2"]]]"#)
+            .contains("trade record does not have 6 or 7 items"));
+        assert!(err(r#"[189,4811424,[["o",1,"0.1","bad"]]]"#)
+            .contains("book record amount: expected float got \"bad\""));
+        assert!(
+            err(r#"[189,4811424,[["i",{"currencyPair":"BTC_BCH","orderBook":[{}]}]]]"#)
+                .contains("initial book orderBook array should contain 2 objects")
+        );
+    }
+
+    #[test]
+    fn heartbeat() {
+        assert!(is_heartbeat("[1010]"));
+        assert!(is_heartbeat(" [ 1010 ]\n"));
+        assert!(!is_heartbeat("[1002,1]"));
+        assert!(!is_heartbeat(
+            r#"[189,4811424,[["o",1,"0.12906425","0.02691207"]]]"#
+        ));
+    }
+
+    #[test]
+    fn push_message_kinds() {
+        match PushMessage::from_str("[1010]") {
+            Ok(PushMessage::Heartbeat) => (),
+            msg => panic!("expected heartbeat {:?}", msg),
+        }
+        match PushMessage::from_str("[1002,1]") {
+            Ok(PushMessage::Ack(ack)) => assert_eq!(
+                ack,
+                Ack {
+                    channel: 1002,
+                    subscribed: true
+                }
+            ),
+            msg => panic!("expected ack {:?}", msg),
+        }
+        match PushMessage::from_str(r#"{"error":"Invalid channel."}"#) {
+            Ok(PushMessage::Error(error)) => assert_eq!(error, "Invalid channel."),
+            msg => panic!("expected error {:?}", msg),
+        }
+        match PushMessage::from_str(r#"[189,4811424,[["o",1,"0.12906425","0.02691207"]]]"#) {
+            Ok(PushMessage::Book(update)) => assert_eq!(update.book_id, 189),
+            msg => panic!("expected book update {:?}", msg),
+        }
+        let ticker = r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#;
+        match PushMessage::from_str(ticker) {
+            Ok(PushMessage::Ticker(ticker)) => assert_eq!(ticker.pair_id, 149),
+            msg => panic!("expected ticker {:?}", msg),
+        }
+        let volume = r#"[1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409"}]]"#;
+        match PushMessage::from_str(volume) {
+            Ok(PushMessage::Volume24h(volume)) => assert_eq!(volume.users, 5804),
+            msg => panic!("expected volume {:?}", msg),
+        }
+        let account = r#"[1000,"",[["b",28,"e","-0.06500000"]]]"#;
+        match PushMessage::from_str(account) {
+            Ok(PushMessage::Account(account)) => assert_eq!(account.records.len(), 1),
+            msg => panic!("expected account update {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn push_message_err() {
+        if let Ok(val) = PushMessage::from_str(r#"{"result":"ok"}"#) {
+            panic!("processed wrong json {:?}", val);
+        }
+        if let Ok(val) = PushMessage::from_str("[1011]") {
+            panic!("processed wrong json {:?}", val);
+        }
+        if let Ok(val) = PushMessage::from_str("[189,4811424]") {
+            panic!("processed wrong json {:?}", val);
+        }
+        if let Ok(val) = PushMessage::from_str(r#"[189,null,[["o",1,"0.1","0.2"]]]"#) {
+            panic!("processed wrong json {:?}", val);
+        }
+        if let Ok(val) = PushMessage::from_str(r#"[1002,null,[149,"382.98901522"]]"#) {
+            panic!("processed wrong json {:?}", val);
+        }
+    }
+
+    #[test]
+    fn json_deserialize_ticker_update() {
+        let ticker = r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#;
+        match TickerUpdate::from_str(ticker) {
+            Ok(ticker) => {
+                assert_eq!(ticker.pair_id, 149);
+                assert_eq!(ticker.last, 382.989_015_22);
+                assert_eq!(ticker.percent_change, -0.043_129_5);
+                assert!(!ticker.is_frozen);
+                assert_eq!(ticker.low_24h, 373.792_040_03);
+            }
+            Err(error) => panic!("failed to process json {}", error),
+        }
+        // newer api versions append fields
+        let appended = ticker.replace(r#""373.79204003"]"#, r#""373.79204003",1]"#);
+        assert_eq!(
+            TickerUpdate::from_str(&appended).unwrap(),
+            TickerUpdate::from_str(ticker).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_deserialize_ticker_update_err() {
+        let ticker = r#"[1002,null,[149,"382.98901522","381.99755898"]]"#;
+        if let Ok(val) = TickerUpdate::from_str(ticker) {
+            panic!("processed wrong json {:?}", val);
+        }
+        let ticker = r#"[189,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#;
+        if let Ok(val) = TickerUpdate::from_str(ticker) {
+            panic!("processed wrong json {:?}", val);
+        }
+    }
+
+    #[test]
+    fn json_deserialize_volume_update() {
+        let volume = r#"[1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409","ETH":"2645.921","USDT":"10832502.689","USDC":"1473758.501"}]]"#;
+        match Volume24h::from_str(volume) {
+            Ok(volume) => {
+                assert_eq!(volume.time.sec, 1_541_607_960);
+                assert_eq!(volume.users, 5804);
+                assert_eq!(volume.volumes["USDT"], 10_832_502.689);
+            }
+            Err(error) => panic!("failed to process json {}", error),
+        }
+        assert_eq!(
+            Volume24h::try_from(json::parse(volume).unwrap()).unwrap(),
+            Volume24h::from_str(volume).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_deserialize_volume_update_err() {
+        let volume = r#"[1003,null,["2018/11/07",5804,{"BTC":"3418.409"}]]"#;
+        if let Ok(val) = Volume24h::from_str(volume) {
+            panic!("processed wrong json {:?}", val);
+        }
+    }
+
+    #[test]
+    fn json_deserialize_account_update() {
+        let update = r#"[1000,"",[["b",28,"e","-0.06500000"],["n",148,6083059,1,"0.03000000","2.00000000","2018-09-08 04:54:09"],["o",6083059,"1.50000000"],["t",12345,"0.03000000","0.50000000","0.00250000",0,6083059,"0.00000375","2018-09-08 05:54:09"]]]"#;
+        let records = match AccountUpdate::from_str(update) {
+            Ok(update) => update.records,
+            Err(error) => panic!("failed to process json {}", error),
+        };
+        match records[0] {
+            AccountRecord::Balance(ref b) => assert_eq!((b.currency_id, b.amount), (28, -0.065)),
+            ref rec => panic!("expected balance {:?}", rec),
+        }
+        match records[1] {
+            AccountRecord::NewOrder(ref n) => {
+                assert_eq!((n.pair_id, n.order_number, n.buy), (148, 6_083_059, true));
+                assert_eq!(n.date.sec, 1_536_382_449);
+            }
+            ref rec => panic!("expected new order {:?}", rec),
+        }
+        match records[2] {
+            AccountRecord::OrderUpdate(ref o) => assert_eq!(o.amount, 1.5),
+            ref rec => panic!("expected order update {:?}", rec),
+        }
+        match records[3] {
+            AccountRecord::Trade(ref t) => {
+                assert_eq!((t.trade_id, t.order_number), (12345, 6_083_059));
+                assert_eq!(t.total_fee, 0.000_003_75);
+            }
+            ref rec => panic!("expected trade {:?}", rec),
+        }
+    }
+
+    #[test]
+    fn json_deserialize_account_update_err() {
+        let update = r#"[1000,"",[["b",28,"e"]]]"#;
+        if let Ok(val) = AccountUpdate::from_str(update) {
+            panic!("processed wrong json {:?}", val);
+        }
+        let update = r#"[1000,"",[["x",28,"e","-0.06500000"]]]"#;
+        if let Ok(val) = AccountUpdate::from_str(update) {
+            panic!("processed wrong json {:?}", val);
+        }
+    }
 }
@@ -1,7 +1,15 @@
 pub mod book;
+pub mod borrowed;
 pub mod json;
+pub mod latency;
+pub mod markets;
 pub mod messages;
+pub mod pair;
+pub mod sequence;
 pub mod stats;
+pub mod ticker;
 pub mod timeseries;
 pub mod trade;
 pub mod tradestats;
+pub mod v3;
+pub mod volume;
@@ -0,0 +1,253 @@
+use crate::error::PoloError;
+use json::JsonValue;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// currency code, ex: BTC, known codes are static and others share one allocation per parse,
+// clone is a reference count increment at most
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Currency(Code);
+
+// known codes are always Static, so derived equality compares codes by content
+// and CurrencyPair constants can be used as match patterns
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Code {
+    Static(&'static str),
+    Shared(Arc<str>),
+}
+
+// currency codes of known pairs, parsing them does not allocate
+const KNOWN: [&str; 7] = ["BTC", "USDT", "ETH", "BCH", "LTC", "ZEC", "XRP"];
+
+// market in BASE_QUOTE format of poloniex public api, ex: BTC_ETH trades ETH for BTC
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CurrencyPair {
+    pub base: Currency,
+    pub quote: Currency,
+}
+
+// markets known before CurrencyPair was introduced, TradePairs::BtcEth and alike keep working
+// in expressions and in match patterns
+#[allow(non_upper_case_globals)]
+impl CurrencyPair {
+    pub const BtcEth: CurrencyPair = CurrencyPair::known("BTC", "ETH");
+    pub const BtcBch: CurrencyPair = CurrencyPair::known("BTC", "BCH");
+    pub const BtcLtc: CurrencyPair = CurrencyPair::known("BTC", "LTC");
+    pub const BtcZec: CurrencyPair = CurrencyPair::known("BTC", "ZEC");
+    pub const UsdtBtc: CurrencyPair = CurrencyPair::known("USDT", "BTC");
+    pub const UsdtEth: CurrencyPair = CurrencyPair::known("USDT", "ETH");
+    pub const UsdtLtc: CurrencyPair = CurrencyPair::known("USDT", "LTC");
+    pub const UsdtBch: CurrencyPair = CurrencyPair::known("USDT", "BCH");
+    pub const UsdtZec: CurrencyPair = CurrencyPair::known("USDT", "ZEC");
+    pub const UsdtXrp: CurrencyPair = CurrencyPair::known("USDT", "XRP");
+}
+
+impl CurrencyPair {
+    // any string is accepted as long as it is a currency code
+    pub fn new(base: &str, quote: &str) -> Result<CurrencyPair, PoloError> {
+        Ok(CurrencyPair {
+            base: Currency::from_str(base)?,
+            quote: Currency::from_str(quote)?,
+        })
+    }
+
+    const fn known(base: &'static str, quote: &'static str) -> CurrencyPair {
+        CurrencyPair {
+            base: Currency(Code::Static(base)),
+            quote: Currency(Code::Static(quote)),
+        }
+    }
+}
+
+impl Currency {
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            Code::Static(code) => code,
+            Code::Shared(ref code) => code,
+        }
+    }
+}
+
+/**
+ * Currency conversion traits
+ * use:
+ *  let currency:: Currency = Currency::from_str("BTC")
+ **/
+
+impl FromStr for Currency {
+    type Err = PoloError;
+    fn from_str(currency: &str) -> Result<Self, Self::Err> {
+        let valid = !currency.is_empty()
+            && currency
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+        if !valid {
+            return Err(PoloError::wrong_data(format!(
+                "wrong currency {:?}",
+                currency
+            )));
+        }
+        Ok(match KNOWN.iter().find(|known| **known == currency) {
+            Some(known) => Currency(Code::Static(known)),
+            None => Currency(Code::Shared(Arc::from(currency))),
+        })
+    }
+}
+
+impl Deref for Currency {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for Currency {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Currency {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Currency {
+    fn partial_cmp(&self, other: &Currency) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Currency {
+    fn cmp(&self, other: &Currency) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/**
+ * CurrencyPair conversion traits
+ * use:
+ *  let pair:: CurrencyPair = CurrencyPair::from_str("BTC_ETH")
+ **/
+
+impl FromStr for CurrencyPair {
+    type Err = PoloError;
+    fn from_str(pair: &str) -> Result<Self, Self::Err> {
+        let mut currencies = pair.split('_');
+        match (currencies.next(), currencies.next(), currencies.next()) {
+            (Some(base), Some(quote), None) => CurrencyPair::new(base, quote),
+            _ => Err(PoloError::wrong_data(format!(
+                "unknown trade pair {:?}",
+                pair
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for CurrencyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_{}", self.base, self.quote)
+    }
+}
+
+impl Serialize for CurrencyPair {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyPair {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pair = String::deserialize(deserializer)?;
+        CurrencyPair::from_str(&pair)
+            .map_err(|_| de::Error::custom(format!("unknown trade pair {:?}", pair)))
+    }
+}
+
+impl<'a> TryFrom<&'a JsonValue> for CurrencyPair {
+    type Error = PoloError;
+    fn try_from(v: &'a JsonValue) -> Result<Self, Self::Error> {
+        serde_json::from_str(&v.dump()).map_err(PoloError::from)
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{Code, CurrencyPair};
+    use crate::data::book::TradePairs;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_pairs() {
+        assert_eq!(
+            CurrencyPair::from_str("BTC_BCH").unwrap(),
+            CurrencyPair::BtcBch
+        );
+        let pair = CurrencyPair::from_str("USDC_ATOM").unwrap();
+        assert_eq!((pair.base.as_str(), pair.quote.as_str()), ("USDC", "ATOM"));
+        assert_eq!(pair.to_string(), "USDC_ATOM");
+        assert_eq!(
+            pair.quote,
+            CurrencyPair::from_str("BTC_ATOM").unwrap().quote
+        );
+        match CurrencyPair::from_str("USDT_BTC").unwrap().base.0 {
+            Code::Static(code) => assert_eq!(code, "USDT"),
+            Code::Shared(code) => panic!("known currency was allocated {:?}", code),
+        }
+        for wrong in &["BTC", "BTC_", "_ETH", "BTC_ETH_LTC", "btc_eth", "1002"] {
+            assert!(CurrencyPair::from_str(wrong).is_err(), "parsed {}", wrong);
+        }
+    }
+
+    #[test]
+    fn match_known_pairs() {
+        let name = |pair: &TradePairs| match *pair {
+            TradePairs::BtcEth => "BtcEth",
+            TradePairs::UsdtBtc => "UsdtBtc",
+            _ => "other",
+        };
+        assert_eq!(name(&TradePairs::BtcEth), "BtcEth");
+        assert_eq!(
+            name(&CurrencyPair::from_str("USDT_BTC").unwrap()),
+            "UsdtBtc"
+        );
+        assert_eq!(name(&CurrencyPair::from_str("USDT_ATOM").unwrap()), "other");
+    }
+
+    #[test]
+    fn json_pairs() {
+        let pairs: HashMap<CurrencyPair, u16> =
+            serde_json::from_str(r#"{"USDT_BTC": 121, "BTC_STR": 89}"#).unwrap();
+        assert_eq!(pairs[&CurrencyPair::UsdtBtc], 121);
+        assert_eq!(pairs[&CurrencyPair::from_str("BTC_STR").unwrap()], 89);
+        assert_eq!(
+            serde_json::to_string(&CurrencyPair::UsdtXrp).unwrap(),
+            r#""USDT_XRP""#
+        );
+        assert!(serde_json::from_str::<CurrencyPair>(r#""BTC""#).is_err());
+    }
+}
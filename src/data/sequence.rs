@@ -0,0 +1,160 @@
+use super::messages::{BookUpdate, RecordUpdate};
+use crate::error::PoloError;
+use std::collections::{BTreeMap, HashMap};
+
+// out of order updates held per book while waiting for a missing one
+pub const DEFAULT_WINDOW: usize = 16;
+
+#[derive(Clone, Debug)]
+enum State {
+    // record_id expected next along with updates received ahead of it
+    Synced {
+        next: u64,
+        pending: BTreeMap<u64, BookUpdate>,
+    },
+    // gap detected, updates are dropped until initial book
+    Invalid,
+}
+
+// orders book updates by record_id per book_id, a book is tracked from its initial snapshot on
+#[derive(Clone, Debug)]
+pub struct Sequencer {
+    window: usize,
+    books: HashMap<u16, State>,
+}
+
+impl Default for Sequencer {
+    fn default() -> Sequencer {
+        Sequencer::new(DEFAULT_WINDOW)
+    }
+}
+
+impl Sequencer {
+    pub fn new(window: usize) -> Sequencer {
+        Sequencer {
+            window,
+            books: HashMap::new(),
+        }
+    }
+
+    // updates ready to be applied in order, duplicates are dropped,
+    // PoloError::SequenceGap is returned once when more than window updates wait for a missing one
+    pub fn push(&mut self, update: BookUpdate) -> Result<Vec<BookUpdate>, PoloError> {
+        let book_id = update.book_id;
+        if is_initial(&update) {
+            let state = State::Synced {
+                next: update.record_id + 1,
+                pending: BTreeMap::new(),
+            };
+            self.books.insert(book_id, state);
+            return Ok(vec![update]);
+        }
+        let state = match self.books.get_mut(&book_id) {
+            Some(state) => state,
+            // book not initialized yet, let the consumer decide
+            None => return Ok(vec![update]),
+        };
+        let (next, pending) = match state {
+            State::Synced { next, pending } => (next, pending),
+            State::Invalid => return Ok(vec![]),
+        };
+        if update.record_id < *next {
+            return Ok(vec![]);
+        }
+        pending.insert(update.record_id, update);
+        let mut ready = vec![];
+        while let Some(update) = pending.remove(next) {
+            *next += 1;
+            ready.push(update);
+        }
+        if pending.len() > self.window {
+            let expected = *next;
+            let got = pending.keys().next().copied().unwrap_or(expected);
+            *state = State::Invalid;
+            return Err(PoloError::SequenceGap(book_id, expected, got));
+        }
+        Ok(ready)
+    }
+
+    // number of out of order updates held per book
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    // false after a gap until the next initial book
+    pub fn is_valid(&self, book_id: u16) -> bool {
+        !matches!(self.books.get(&book_id), Some(State::Invalid))
+    }
+
+    // stop tracking book, ex: after resubscription
+    pub fn forget(&mut self, book_id: u16) {
+        self.books.remove(&book_id);
+    }
+}
+
+pub(crate) fn is_initial(update: &BookUpdate) -> bool {
+    update
+        .records
+        .iter()
+        .any(|rec| matches!(rec, RecordUpdate::Initial(_)))
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::Sequencer;
+    use crate::data::book::Book;
+    use crate::data::messages::{BookUpdate, RecordUpdate};
+    use crate::error::PoloError;
+
+    fn update(record_id: u64) -> BookUpdate {
+        BookUpdate {
+            book_id: 189,
+            record_id,
+            records: vec![],
+        }
+    }
+
+    fn initial(record_id: u64) -> BookUpdate {
+        BookUpdate {
+            book_id: 189,
+            record_id,
+            records: vec![RecordUpdate::Initial(Book::default())],
+        }
+    }
+
+    fn ids(updates: Vec<BookUpdate>) -> Vec<u64> {
+        updates.iter().map(|update| update.record_id).collect()
+    }
+
+    #[test]
+    fn reorder_within_window() {
+        let mut sequencer = Sequencer::new(2);
+        assert_eq!(ids(sequencer.push(update(7)).unwrap()), vec![7]);
+        assert_eq!(ids(sequencer.push(initial(10)).unwrap()), vec![10]);
+        assert_eq!(ids(sequencer.push(update(12)).unwrap()), Vec::<u64>::new());
+        assert_eq!(ids(sequencer.push(update(13)).unwrap()), Vec::<u64>::new());
+        assert_eq!(ids(sequencer.push(update(11)).unwrap()), vec![11, 12, 13]);
+        assert_eq!(ids(sequencer.push(update(12)).unwrap()), Vec::<u64>::new());
+        assert_eq!(ids(sequencer.push(update(14)).unwrap()), vec![14]);
+    }
+
+    #[test]
+    fn gap_until_initial() {
+        let mut sequencer = Sequencer::new(1);
+        sequencer.push(initial(10)).unwrap();
+        assert_eq!(ids(sequencer.push(update(12)).unwrap()), Vec::<u64>::new());
+        match sequencer.push(update(13)) {
+            Err(PoloError::SequenceGap(189, 11, 12)) => (),
+            res => panic!("expected gap {:?}", res),
+        }
+        assert!(!sequencer.is_valid(189));
+        assert_eq!(ids(sequencer.push(update(11)).unwrap()), Vec::<u64>::new());
+        assert_eq!(ids(sequencer.push(initial(20)).unwrap()), vec![20]);
+        assert!(sequencer.is_valid(189));
+        assert_eq!(ids(sequencer.push(update(21)).unwrap()), vec![21]);
+    }
+}
@@ -188,9 +188,15 @@ impl BookAccounting for BookWithStats {
         prev_amount
     }
 
-    fn new_deal(&mut self, id: u64, rate: String, amount: f64) -> Result<f64, PoloError> {
+    fn new_deal(
+        &mut self,
+        id: u64,
+        rate: String,
+        amount: f64,
+        exchange_time: Timespec,
+    ) -> Result<f64, PoloError> {
         self.last_updated = get_time();
-        let rate_f64 = self.book.new_deal(id, rate, amount)?;
+        let rate_f64 = self.book.new_deal(id, rate, amount, exchange_time)?;
         Ok(rate_f64)
     }
 
@@ -288,13 +294,12 @@ fn update_sorted_vec(
 mod tests {
     use super::BookWithStats;
     use crate::data::book::Book;
-    use json;
-    use std::convert::TryFrom;
+    use std::str::FromStr;
 
     #[test]
     fn stats_init() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let book_stats = BookWithStats::new(book).stats;
         assert_eq!(book_stats.min_sell, 0.131_619_01);
         assert_eq!(book_stats.max_buy, 0.131_096_21);
@@ -303,7 +308,7 @@ mod tests {
     #[test]
     fn stats_init_wrong_order() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13361901": 0.23709568, "0.13164313": "0.17328089"}, {"0.12909621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let book_stats = BookWithStats::new(book).stats;
         assert_eq!(book_stats.min_sell, 0.131_643_13);
         assert_eq!(book_stats.max_buy, 0.130_696_21);
@@ -312,7 +317,7 @@ mod tests {
     #[test]
     fn stats_update_sell_zero() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_sell_orders(0.1, 0.0, None);
         assert_eq!(book_stats.min_sell, 0.131_619_01);
@@ -321,7 +326,7 @@ mod tests {
     #[test]
     fn stats_update_sell_shift_zero() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_sell_orders(0.131_619_01, 0.0, Some(0.237_095_68));
         assert_eq!(book_stats.min_sell, 0.131_643_13);
@@ -330,7 +335,7 @@ mod tests {
     #[test]
     fn stats_update_sell() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_sell_orders(0.1, 1.0, None);
         assert_eq!(book_stats.min_sell, 0.1);
@@ -339,7 +344,7 @@ mod tests {
     #[test]
     fn stats_update_buy_zero() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_buy_orders(100.0, 0.0, None);
         assert_eq!(book_stats.max_buy, 0.131_096_21);
@@ -348,7 +353,7 @@ mod tests {
     #[test]
     fn stats_update_buy_shift_zero() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_buy_orders(0.131_096_21, 0.0, Some(0.2331));
         assert_eq!(book_stats.max_buy, 0.130_696_21);
@@ -357,7 +362,7 @@ mod tests {
     #[test]
     fn stats_update_buy() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568, "0.13164313": "0.17328089"}, {"0.13109621": 0.2331, "0.13069621": 0.2331}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_buy_orders(100.0, 1.0, None);
         assert_eq!(book_stats.max_buy, 100.0);
@@ -366,7 +371,7 @@ mod tests {
     #[test]
     fn stats_skin() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.1111": 100.0, "0.1112": 100.0, "0.1113": 1000.0}, {"0.1003": 1.0, "0.1002": 1.0, "0.1001": 10.0}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let book_stats = BookWithStats::new(book).stats;
         assert_eq!(book_stats.skin_sell, 0.1112);
         assert_eq!(book_stats.skin_buy, 0.1002);
@@ -375,7 +380,7 @@ mod tests {
     #[test]
     fn stats_surface() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.1110": 10.0, "0.1111": 100.0, "0.1112": 100.0, "0.1113": 1000.0}, {"0.1004": 0.1, "0.1003": 1.0, "0.1002": 1.0, "0.1001": 10.0}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let book_stats = BookWithStats::new(book).stats;
         assert_eq!(book_stats.surface_sell, 0.1111);
         assert_eq!(book_stats.surface_buy, 0.1003);
@@ -384,7 +389,7 @@ mod tests {
     #[test]
     fn stats_surface_update() {
         let book_init = r#"{"currencyPair": "BTC_BCH", "orderBook": [{"0.1110": 10.0, "0.1111": 100.0, "0.1112": 100.0, "0.1113": 1000.0}, {"0.1004": 0.1, "0.1003": 1.0, "0.1002": 1.0, "0.1001": 10.0}]}"#;
-        let book = Book::try_from(&json::parse(book_init).unwrap()).unwrap();
+        let book = Book::from_str(book_init).unwrap();
         let mut book_stats = BookWithStats::new(book).stats;
         book_stats.update_sell_orders(0.1109, 10.0, None);
         book_stats.update_buy_orders(0.1005, 0.1, None);
@@ -0,0 +1,140 @@
+use super::markets::MarketRegistry;
+use super::messages::TickerUpdate;
+use super::pair::CurrencyPair;
+use crate::get_time;
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::Timespec;
+
+// latest ticker of every market keyed by currency pair,
+// pair ids of ticker updates are resolved through market registry
+#[derive(Debug, PartialEq)]
+pub struct TickerTable {
+    pub last_updated: Timespec,
+    pub by_pair: HashMap<CurrencyPair, TickerUpdate>,
+    // tickers which pair id is not in registry yet, keyed by pair id
+    pub unresolved: HashMap<u16, TickerUpdate>,
+    registry: Arc<MarketRegistry>,
+}
+
+// TickerTable operations
+impl TickerTable {
+    pub fn new() -> TickerTable {
+        TickerTable::with_registry(Arc::new(MarketRegistry::new()))
+    }
+
+    pub fn with_registry(registry: Arc<MarketRegistry>) -> TickerTable {
+        TickerTable {
+            last_updated: get_time(),
+            by_pair: HashMap::new(),
+            unresolved: HashMap::new(),
+            registry,
+        }
+    }
+
+    // replace registry, ex: after markets were listed, unresolved tickers are keyed by pair if possible
+    pub fn set_registry(&mut self, registry: Arc<MarketRegistry>) {
+        self.registry = registry;
+        let unresolved: Vec<TickerUpdate> = self.unresolved.drain().map(|(_, t)| t).collect();
+        for ticker in unresolved {
+            self.insert(ticker);
+        }
+    }
+
+    // should return previous ticker of the same market OR None
+    pub fn update(&mut self, ticker: TickerUpdate) -> Option<TickerUpdate> {
+        self.last_updated = get_time();
+        self.insert(ticker)
+    }
+
+    pub fn get(&self, pair: &CurrencyPair) -> Option<&TickerUpdate> {
+        self.by_pair.get(pair)
+    }
+
+    // ticker by currency pair id of push api, ex: 149 for USDT_LTC
+    pub fn get_by_id(&self, pair_id: u16) -> Option<&TickerUpdate> {
+        match self.registry.pair(pair_id) {
+            Some(pair) => self.by_pair.get(&pair),
+            None => self.unresolved.get(&pair_id),
+        }
+    }
+
+    // resolved markets sorted by currency pair
+    pub fn markets(&self) -> Vec<(&CurrencyPair, &TickerUpdate)> {
+        let mut markets: Vec<_> = self.by_pair.iter().collect();
+        markets.sort_unstable_by_key(|(pair, _)| *pair);
+        markets
+    }
+
+    fn insert(&mut self, ticker: TickerUpdate) -> Option<TickerUpdate> {
+        match self.registry.pair(ticker.pair_id) {
+            Some(pair) => self.by_pair.insert(pair, ticker),
+            None => self.unresolved.insert(ticker.pair_id, ticker),
+        }
+    }
+}
+
+impl Default for TickerTable {
+    fn default() -> TickerTable {
+        TickerTable::new()
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::TickerTable;
+    use crate::data::markets::MarketRegistry;
+    use crate::data::messages::TickerUpdate;
+    use crate::data::pair::CurrencyPair;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    const TICKER_149: &str = r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#;
+    const TICKER_121: &str = r#"[1002,null,[121,"6416.00000000","6416.00000003","6415.99999999","0.00157135","5961427.36519937","930.85592542",0,"6445.00000000","6350.00000000"]]"#;
+    const MARKETS: &str = r#"[
+        {"id":121,"pair":"USDT_BTC","baseId":214,"quoteId":28,"active":true,"frozen":false},
+        {"id":149,"pair":"USDT_LTC","baseId":214,"quoteId":125,"active":true,"frozen":false}
+    ]"#;
+
+    fn registry() -> Arc<MarketRegistry> {
+        Arc::new(MarketRegistry::from_str(MARKETS).unwrap())
+    }
+
+    #[test]
+    fn ticker_update() {
+        let mut table = TickerTable::with_registry(registry());
+        let ticker = TickerUpdate::from_str(TICKER_149).unwrap();
+        assert_eq!(table.update(ticker.clone()), None);
+        assert_eq!(table.update(ticker.clone()), Some(ticker.clone()));
+        assert_eq!(table.get(&CurrencyPair::UsdtLtc), Some(&ticker));
+        assert_eq!(table.get_by_id(149), Some(&ticker));
+        assert_eq!(table.get(&CurrencyPair::UsdtBtc), None);
+        assert_eq!(table.get_by_id(121), None);
+    }
+
+    #[test]
+    fn ticker_markets() {
+        let mut table = TickerTable::with_registry(registry());
+        table.update(TickerUpdate::from_str(TICKER_149).unwrap());
+        table.update(TickerUpdate::from_str(TICKER_121).unwrap());
+        let pairs: Vec<String> = table.markets().iter().map(|(p, _)| p.to_string()).collect();
+        assert_eq!(pairs, vec!["USDT_BTC", "USDT_LTC"]);
+    }
+
+    #[test]
+    fn resolve_later() {
+        let mut table = TickerTable::new();
+        let ticker = TickerUpdate::from_str(TICKER_149).unwrap();
+        table.update(ticker.clone());
+        assert_eq!(table.get(&CurrencyPair::UsdtLtc), None);
+        assert_eq!(table.get_by_id(149), Some(&ticker));
+        table.set_registry(registry());
+        assert!(table.unresolved.is_empty());
+        assert_eq!(table.get(&CurrencyPair::UsdtLtc), Some(&ticker));
+        assert_eq!(table.get_by_id(149), Some(&ticker));
+    }
+}
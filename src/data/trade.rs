@@ -25,6 +25,12 @@ impl TradeBook {
     }
 
     pub fn add_book(&mut self, book: Book, id: u16) {
+        let idx = self.add_pair_book(book);
+        self.by_id.insert(id, idx);
+    }
+
+    // add book without channel id, ex: v3 api addresses books by symbol, returns book index
+    pub fn add_pair_book(&mut self, book: Book) -> usize {
         let pair = book.pair.clone();
         let idx: usize;
         if let Some(i) = self.by_pair.get(&pair) {
@@ -34,8 +40,34 @@ impl TradeBook {
             self.books.push(BookWithStats::new(book));
             idx = self.books.len() - 1;
         }
-        self.by_id.insert(id, idx);
         self.by_pair.insert(pair, idx);
+        idx
+    }
+
+    // detach books of given pairs from their channel ids and drop their orders,
+    // book_by_id will not find them until next add_book, returns detached ids
+    pub fn invalidate(&mut self, pairs: &[TradePairs]) -> Vec<u16> {
+        let idxs: Vec<usize> = pairs
+            .iter()
+            .filter_map(|pair| self.by_pair.get(pair).copied())
+            .collect();
+        let ids: Vec<u16> = self
+            .by_id
+            .iter()
+            .filter(|(_, idx)| idxs.contains(idx))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids.iter() {
+            self.invalidate_id(*id);
+        }
+        ids
+    }
+
+    // the same as invalidate for a single channel id
+    pub fn invalidate_id(&mut self, id: u16) {
+        if let Some(idx) = self.by_id.remove(&id) {
+            self.books[idx].reset_orders();
+        }
     }
 
     pub fn book_by_id(&mut self, id: u16) -> Option<&mut dyn BookAccounting> {
@@ -45,4 +77,12 @@ impl TradeBook {
             None
         }
     }
+
+    pub fn book_by_pair(&mut self, pair: &TradePairs) -> Option<&mut dyn BookAccounting> {
+        if let Some(idx) = self.by_pair.get(pair) {
+            Some(&mut self.books[*idx])
+        } else {
+            None
+        }
+    }
 }
@@ -136,6 +136,7 @@ mod tests {
     fn stats_new() {
         let deal = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 1,
             rate: 0.1,
             amount: 10.0,
@@ -149,6 +150,7 @@ mod tests {
     fn stats_add_deal() {
         let deal = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 1,
             rate: 0.1,
             amount: 10.0,
@@ -161,12 +163,14 @@ mod tests {
     fn stats_deals() {
         let deal1 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 1,
             rate: 0.1,
             amount: 10.0,
         };
         let deal2 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 2,
             rate: 0.1,
             amount: -10.0,
@@ -186,12 +190,14 @@ mod tests {
     fn stats_sub() {
         let deal1 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 1,
             rate: 0.1,
             amount: 10.0,
         };
         let deal2 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 2,
             rate: 0.1,
             amount: -10.0,
@@ -207,12 +213,14 @@ mod tests {
     fn stats_sub_exact() {
         let deal1 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 1,
             rate: 0.1,
             amount: 10.0,
         };
         let deal2 = Deal {
             time: TIME,
+            exchange_time: TIME,
             id: 2,
             rate: 0.1,
             amount: -10.0,
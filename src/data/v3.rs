@@ -0,0 +1,324 @@
+use super::book::{Book, TradePairs};
+use super::json::Num;
+use super::messages::{epoch_ms_time, BookRecord, RecordUpdate, TradeRecord};
+use crate::error::PoloError;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::str::FromStr;
+
+// spot websocket api v3, public channels are named and frames are json objects:
+// {"channel":"book_lv2","action":"update","data":[{"symbol":"BTC_USDT",...}]}
+// symbols put base currency first, so legacy USDT_BTC is BTC_USDT
+pub const PUBLIC_URL: &str = "wss://ws.poloniex.com/ws/public";
+
+pub const BOOK_CHANNEL: &str = "book_lv2";
+pub const TRADES_CHANNEL: &str = "trades";
+pub const TICKER_CHANNEL: &str = "ticker";
+
+// book_lv2 item, snapshot carries the whole book as RecordUpdate::Initial,
+// update carries SellTotal/BuyTotal records stamped with ts
+// ex: {"symbol":"BTC_USDT","createTime":1652774727337,"asks":[["40001","0.1"]],"bids":[["40000.1","0"]],"lastId":164148724,"id":164148725,"ts":1652774727344}
+#[derive(Debug, Clone, PartialEq)]
+pub struct V3BookUpdate {
+    pub pair: TradePairs,
+    // update sequence number, update follows the one with id == last_id
+    pub id: u64,
+    pub last_id: u64,
+    pub records: Vec<RecordUpdate>,
+}
+
+// trades item as RecordUpdate::Sell or RecordUpdate::Buy by taker side
+// ex: {"symbol":"BTC_USDT","amount":"70","takerSide":"buy","quantity":"4","createTime":1648059516810,"price":"17.5","id":"194","ts":1648059516832}
+#[derive(Debug, Clone, PartialEq)]
+pub struct V3Trade {
+    pub pair: TradePairs,
+    pub record: RecordUpdate,
+}
+
+// any message received via public v3 api
+#[derive(Debug, Clone, PartialEq)]
+pub enum V3Message {
+    Books(Vec<V3BookUpdate>),
+    Trades(Vec<V3Trade>),
+    // subscription acknowledgement, ex: {"event":"subscribe","channel":"book_lv2","symbols":["BTC_USDT"]}
+    Subscribed(String),
+    Unsubscribed(String),
+    // reply to {"event":"ping"}
+    Pong,
+    // ex: {"event":"error","message":"Subscription failed"}
+    Error(String),
+    // channel which is not decoded, ex: ticker
+    Other(String),
+}
+
+// BTC_USDT -> TradePairs::UsdtBtc
+pub fn pair_from_symbol(symbol: &str) -> Result<TradePairs, PoloError> {
+    let pair = TradePairs::from_str(symbol)
+        .map_err(|_| PoloError::wrong_data(format!("unknown v3 symbol {:?}", symbol)))?;
+    Ok(TradePairs {
+        base: pair.quote,
+        quote: pair.base,
+    })
+}
+
+// TradePairs::UsdtBtc -> BTC_USDT
+pub fn symbol(pair: &TradePairs) -> String {
+    format!("{}_{}", pair.quote, pair.base)
+}
+
+#[derive(Deserialize)]
+struct Frame<'a> {
+    event: Option<String>,
+    channel: Option<String>,
+    action: Option<String>,
+    message: Option<String>,
+    #[serde(borrow)]
+    data: Option<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BookItem {
+    symbol: String,
+    asks: Vec<(String, Num<f64>)>,
+    bids: Vec<(String, Num<f64>)>,
+    last_id: u64,
+    id: u64,
+    ts: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TradeItem {
+    symbol: String,
+    taker_side: String,
+    quantity: Num<f64>,
+    price: String,
+    id: Num<u64>,
+    create_time: u64,
+}
+
+impl BookItem {
+    fn into_update(self, snapshot: bool) -> Result<V3BookUpdate, PoloError> {
+        let pair = pair_from_symbol(&self.symbol)?;
+        let records = if snapshot {
+            let mut book = Book::new(pair.clone());
+            book.sell = self
+                .asks
+                .into_iter()
+                .map(|(rate, amount)| (rate, amount.0))
+                .collect();
+            book.buy = self
+                .bids
+                .into_iter()
+                .map(|(rate, amount)| (rate, amount.0))
+                .collect();
+            vec![RecordUpdate::Initial(book)]
+        } else {
+            let ts = self.ts;
+            let record = |(rate, amount): (String, Num<f64>)| BookRecord {
+                rate,
+                amount: amount.0,
+                epoch_ms: Some(ts),
+            };
+            let sell = self
+                .asks
+                .into_iter()
+                .map(record)
+                .map(RecordUpdate::SellTotal);
+            let buy = self
+                .bids
+                .into_iter()
+                .map(record)
+                .map(RecordUpdate::BuyTotal);
+            sell.chain(buy).collect()
+        };
+        Ok(V3BookUpdate {
+            pair,
+            id: self.id,
+            last_id: self.last_id,
+            records,
+        })
+    }
+}
+
+impl TradeItem {
+    fn into_trade(self) -> Result<V3Trade, PoloError> {
+        let record = TradeRecord {
+            id: self.id.0,
+            rate: self.price,
+            amount: self.quantity.0,
+            exchange_time: epoch_ms_time(self.create_time),
+            epoch_ms: Some(self.create_time),
+        };
+        let record = match self.taker_side.as_str() {
+            "sell" => RecordUpdate::Sell(record),
+            "buy" => RecordUpdate::Buy(record),
+            side => {
+                return Err(PoloError::wrong_data(format!(
+                    "trade has unknown taker side {:?}",
+                    side
+                )))
+            }
+        };
+        Ok(V3Trade {
+            pair: pair_from_symbol(&self.symbol)?,
+            record,
+        })
+    }
+}
+
+/**
+ * V3Message conversion traits
+ * use:
+ *  let msg:: V3Message = V3Message::from_str(
+ *    r#"{"channel":"trades","data":[{"symbol":"BTC_USDT","amount":"70","takerSide":"buy","quantity":"4","createTime":1648059516810,"price":"17.5","id":"194","ts":1648059516832}]}"#
+ *  )
+ **/
+
+impl FromStr for V3Message {
+    type Err = PoloError;
+    fn from_str(msg: &str) -> Result<Self, Self::Err> {
+        let frame: Frame = serde_json::from_str(msg)?;
+        let err = |msg: &str| Err(PoloError::wrong_data(msg.to_owned()));
+        if let Some(event) = frame.event {
+            let channel = frame.channel.unwrap_or_default();
+            return match event.as_str() {
+                "subscribe" => Ok(V3Message::Subscribed(channel)),
+                "unsubscribe" => Ok(V3Message::Unsubscribed(channel)),
+                "pong" => Ok(V3Message::Pong),
+                "error" => Ok(V3Message::Error(frame.message.unwrap_or_default())),
+                _ => err("v3 message has unknown event"),
+            };
+        }
+        let channel = match frame.channel {
+            Some(channel) => channel,
+            None => return err("v3 message has neither event nor channel"),
+        };
+        let data = match frame.data {
+            Some(data) => data.get(),
+            None => return err("v3 message has no data"),
+        };
+        match channel.as_str() {
+            BOOK_CHANNEL => {
+                let snapshot = match frame.action.as_deref() {
+                    Some("snapshot") => true,
+                    Some("update") => false,
+                    _ => return err("book_lv2 message has unknown action"),
+                };
+                let items: Vec<BookItem> = serde_json::from_str(data)?;
+                let updates = items
+                    .into_iter()
+                    .map(|item| item.into_update(snapshot))
+                    .collect::<Result<_, _>>()?;
+                Ok(V3Message::Books(updates))
+            }
+            TRADES_CHANNEL => {
+                let items: Vec<TradeItem> = serde_json::from_str(data)?;
+                let trades = items
+                    .into_iter()
+                    .map(TradeItem::into_trade)
+                    .collect::<Result<_, _>>()?;
+                Ok(V3Message::Trades(trades))
+            }
+            _ => Ok(V3Message::Other(channel)),
+        }
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{pair_from_symbol, symbol, V3BookUpdate, V3Message, V3Trade};
+    use crate::data::book::TradePairs;
+    use crate::data::messages::{BookRecord, RecordUpdate, TradeRecord};
+    use std::str::FromStr;
+    use time::Timespec;
+
+    #[test]
+    fn symbols() {
+        assert_eq!(pair_from_symbol("BTC_USDT").unwrap(), TradePairs::UsdtBtc);
+        assert_eq!(pair_from_symbol("BCH_BTC").unwrap(), TradePairs::BtcBch);
+        assert_eq!(pair_from_symbol("ETH_SOL").unwrap().to_string(), "SOL_ETH");
+        assert!(pair_from_symbol("BTCUSDT").is_err());
+        assert_eq!(symbol(&TradePairs::UsdtEth), "ETH_USDT");
+    }
+
+    #[test]
+    fn json_deserialize_book() {
+        let msg = r#"{"channel":"book_lv2","action":"snapshot","data":[{"symbol":"BTC_USDT","createTime":1652774727337,"asks":[["40001","0.1"]],"bids":[["40000.1","0.5"],["39999","2"]],"lastId":164148724,"id":164148725,"ts":1652774727344}]}"#;
+        match V3Message::from_str(msg).unwrap() {
+            V3Message::Books(ref updates) => match updates[0].records[..] {
+                [RecordUpdate::Initial(ref book)] => {
+                    assert_eq!(book.pair, TradePairs::UsdtBtc);
+                    assert_eq!(book.sell["40001"], 0.1);
+                    assert_eq!(book.buy.len(), 2);
+                }
+                ref records => panic!("expected initial book {:?}", records),
+            },
+            msg => panic!("expected book {:?}", msg),
+        }
+
+        let msg = r#"{"channel":"book_lv2","action":"update","data":[{"symbol":"BTC_USDT","createTime":1652774727337,"asks":[],"bids":[["40000.1","0"]],"lastId":164148725,"id":164148726,"ts":1652774727350}]}"#;
+        assert_eq!(
+            V3Message::from_str(msg).unwrap(),
+            V3Message::Books(vec![V3BookUpdate {
+                pair: TradePairs::UsdtBtc,
+                id: 164_148_726,
+                last_id: 164_148_725,
+                records: vec![RecordUpdate::BuyTotal(BookRecord {
+                    rate: "40000.1".to_owned(),
+                    amount: 0.0,
+                    epoch_ms: Some(1_652_774_727_350),
+                })],
+            }])
+        );
+    }
+
+    #[test]
+    fn json_deserialize_trades() {
+        let msg = r#"{"channel":"trades","data":[{"symbol":"ETH_USDT","amount":"70","takerSide":"sell","quantity":"4","createTime":1648059516810,"price":"17.5","id":"194","ts":1648059516832}]}"#;
+        assert_eq!(
+            V3Message::from_str(msg).unwrap(),
+            V3Message::Trades(vec![V3Trade {
+                pair: TradePairs::UsdtEth,
+                record: RecordUpdate::Sell(TradeRecord {
+                    id: 194,
+                    rate: "17.5".to_owned(),
+                    amount: 4.0,
+                    exchange_time: Timespec::new(1_648_059_516, 810_000_000),
+                    epoch_ms: Some(1_648_059_516_810),
+                }),
+            }])
+        );
+    }
+
+    #[test]
+    fn json_deserialize_events() {
+        let msg = r#"{"event":"subscribe","channel":"book_lv2","symbols":["BTC_USDT"]}"#;
+        assert_eq!(
+            V3Message::from_str(msg).unwrap(),
+            V3Message::Subscribed("book_lv2".to_owned())
+        );
+        assert_eq!(
+            V3Message::from_str(r#"{"event":"pong"}"#).unwrap(),
+            V3Message::Pong
+        );
+        assert_eq!(
+            V3Message::from_str(r#"{"event":"error","message":"Subscription failed"}"#).unwrap(),
+            V3Message::Error("Subscription failed".to_owned())
+        );
+        let msg = r#"{"channel":"ticker","data":[{"symbol":"BTC_USDT","close":"40000"}]}"#;
+        assert_eq!(
+            V3Message::from_str(msg).unwrap(),
+            V3Message::Other("ticker".to_owned())
+        );
+        assert!(V3Message::from_str(r#"{"channel":"book_lv2","data":[]}"#).is_err());
+        assert!(
+            V3Message::from_str(r#"{"channel":"trades","data":[{"symbol":"BTC_USDT"}]}"#).is_err()
+        );
+    }
+}
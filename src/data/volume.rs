@@ -0,0 +1,68 @@
+use super::messages::Volume24h;
+use super::timeseries::{Timeseries, WithTime};
+use time::Timespec;
+
+impl WithTime for Volume24h {
+    fn get_time(&self) -> Timespec {
+        self.time
+    }
+}
+
+// latest 24h exchange volume along with its history
+#[derive(Debug, Default, PartialEq)]
+pub struct VolumeStore {
+    pub latest: Option<Volume24h>,
+    pub history: Timeseries<Volume24h>,
+}
+
+// VolumeStore operations
+impl VolumeStore {
+    pub fn new() -> VolumeStore {
+        VolumeStore::default()
+    }
+
+    // poloniex repeats the same minute after resubscribe, only newer values are stored
+    pub fn update(&mut self, volume: Volume24h) -> bool {
+        if let Some(ref latest) = self.latest {
+            if volume.time <= latest.time {
+                return false;
+            }
+        }
+        self.history.add(volume.clone());
+        self.latest = Some(volume);
+        true
+    }
+
+    // latest volume by base currency
+    pub fn volume(&self, currency: &str) -> Option<f64> {
+        self.latest
+            .as_ref()
+            .and_then(|latest| latest.volumes.get(currency).cloned())
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::VolumeStore;
+    use crate::data::messages::Volume24h;
+    use std::str::FromStr;
+
+    #[test]
+    fn volume_update() {
+        let mut store = VolumeStore::new();
+        let first =
+            r#"[1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409","USDT":"10832502.689"}]]"#;
+        let second =
+            r#"[1003,null,["2018-11-07 16:27",5806,{"BTC":"3419.001","USDT":"10832602.689"}]]"#;
+        assert!(store.update(Volume24h::from_str(first).unwrap()));
+        assert!(store.update(Volume24h::from_str(second).unwrap()));
+        assert!(!store.update(Volume24h::from_str(first).unwrap()));
+        assert_eq!(store.volume("BTC"), Some(3419.001));
+        assert_eq!(store.volume("ETH"), None);
+        assert_eq!(store.history.data.len(), 2);
+    }
+}
@@ -1,10 +1,13 @@
 use json;
+use serde_json;
 use std::error;
 use std::fmt;
 use std::io;
 use std::io::ErrorKind::InvalidData;
 use std::num::{ParseFloatError, ParseIntError};
 use std::sync::mpsc::RecvError;
+#[cfg(feature = "ws")]
+use tungstenite;
 
 #[derive(Debug)]
 pub enum PoloError {
@@ -12,7 +15,16 @@ pub enum PoloError {
     ParseInt(ParseIntError),
     Type(io::Error),
     Json(json::Error),
+    SerdeJson(serde_json::Error),
     Receive(RecvError),
+    // push message parse error along with message raw text
+    Decode(Box<PoloError>, String),
+    // channel subscription rejected by server with error
+    Subscription(String, String),
+    // book update missing: book_id, expected record_id, received record_id
+    SequenceGap(u16, u64, u64),
+    #[cfg(feature = "ws")]
+    WebSocket(tungstenite::Error),
 }
 
 impl PoloError {
@@ -28,7 +40,19 @@ impl fmt::Display for PoloError {
             PoloError::ParseFloat(ref err) => write!(f, "Parse error: {}", err),
             PoloError::ParseInt(ref err) => write!(f, "Parse error: {}", err),
             PoloError::Json(ref err) => write!(f, "Json error: {}", err),
+            PoloError::SerdeJson(ref err) => write!(f, "Json error: {}", err),
             PoloError::Receive(ref err) => write!(f, "Receive error: {}", err),
+            PoloError::Decode(ref err, ref raw) => write!(f, "Decode error: {} in {}", err, raw),
+            PoloError::Subscription(ref channel, ref err) => {
+                write!(f, "Subscription error: {} for channel {}", err, channel)
+            }
+            PoloError::SequenceGap(book_id, expected, got) => write!(
+                f,
+                "Sequence gap: book {} expected record {} got {}",
+                book_id, expected, got
+            ),
+            #[cfg(feature = "ws")]
+            PoloError::WebSocket(ref err) => write!(f, "WebSocket error: {}", err),
         }
     }
 }
@@ -40,7 +64,13 @@ impl error::Error for PoloError {
             PoloError::ParseFloat(ref err) => err.description(),
             PoloError::ParseInt(ref err) => err.description(),
             PoloError::Json(ref err) => err.description(),
+            PoloError::SerdeJson(ref err) => err.description(),
             PoloError::Receive(ref err) => err.description(),
+            PoloError::Decode(ref err, _) => err.description(),
+            PoloError::Subscription(_, ref err) => err,
+            PoloError::SequenceGap(..) => "book update sequence gap",
+            #[cfg(feature = "ws")]
+            PoloError::WebSocket(ref err) => err.description(),
         }
     }
 
@@ -54,7 +84,13 @@ impl error::Error for PoloError {
             PoloError::ParseFloat(ref err) => Some(err),
             PoloError::ParseInt(ref err) => Some(err),
             PoloError::Json(ref err) => Some(err),
+            PoloError::SerdeJson(ref err) => Some(err),
             PoloError::Receive(ref err) => Some(err),
+            PoloError::Decode(ref err, _) => Some(err.as_ref()),
+            PoloError::Subscription(_, _) => None,
+            PoloError::SequenceGap(..) => None,
+            #[cfg(feature = "ws")]
+            PoloError::WebSocket(ref err) => Some(err),
         }
     }
 }
@@ -83,8 +119,21 @@ impl From<json::Error> for PoloError {
     }
 }
 
+impl From<serde_json::Error> for PoloError {
+    fn from(err: serde_json::Error) -> PoloError {
+        PoloError::SerdeJson(err)
+    }
+}
+
 impl From<RecvError> for PoloError {
     fn from(err: RecvError) -> PoloError {
         PoloError::Receive(err)
     }
 }
+
+#[cfg(feature = "ws")]
+impl From<tungstenite::Error> for PoloError {
+    fn from(err: tungstenite::Error) -> PoloError {
+        PoloError::WebSocket(err)
+    }
+}
@@ -3,6 +3,8 @@ pub mod data;
 pub mod error;
 #[cfg(feature = "ws")]
 pub mod push;
+#[cfg(all(feature = "ws", any(test, feature = "mock")))]
+pub mod mock;
 
 #[cfg(not(target_arch="wasm32"))]
 pub(crate) use time::get_time;
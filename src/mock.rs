@@ -0,0 +1,382 @@
+use crate::data::messages::{BookUpdate, HEARTBEAT_CHANNEL};
+use futures::channel::oneshot;
+use futures::{future, SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::delay_for;
+use tungstenite::error::Error;
+use tungstenite::protocol::Message;
+
+// scripted server behaviour, steps are played in order across connections:
+// after Disconnect next accepted connection continues with the following step,
+// see MockServer::replicated for a script played from start on every connection
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    // text frame sent as is, e.g. recorded BookUpdate
+    Frame(String),
+    Heartbeat,
+    // wait until client subscribes channel on current connection
+    AwaitSubscribe(String),
+    Pause(Duration),
+    // close current connection
+    Disconnect,
+}
+
+// local websocket server speaking poloniex push protocol:
+//  - numeric channels subscriptions are acknowledged with [channel, 1]
+//  - everything else is sent by script
+// server stops when dropped
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    _stop: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct State {
+    steps: Mutex<VecDeque<Step>>,
+    // script copied to every connection instead of shared steps
+    replica: Option<VecDeque<Step>>,
+    subscriptions: Mutex<Vec<String>>,
+    connections: AtomicUsize,
+}
+
+impl MockServer {
+    // bind to random localhost port and start serving script
+    // should be called within tokio runtime
+    pub async fn start(steps: Vec<Step>) -> io::Result<MockServer> {
+        MockServer::with_state(State {
+            steps: Mutex::new(steps.into()),
+            ..State::default()
+        })
+        .await
+    }
+
+    // every connection plays the whole script, e.g. legs of push::subscribe_redundant
+    pub async fn replicated(steps: Vec<Step>) -> io::Result<MockServer> {
+        MockServer::with_state(State {
+            replica: Some(steps.into()),
+            ..State::default()
+        })
+        .await
+    }
+
+    async fn with_state(state: State) -> io::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(state);
+        let (stop, stopped) = oneshot::channel();
+        tokio::spawn(future::select(
+            Box::pin(serve(listener, state.clone())),
+            stopped,
+        ));
+        Ok(MockServer {
+            addr,
+            state,
+            _stop: stop,
+        })
+    }
+
+    // address to pass to push::subscribe
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    // channels subscribed on connection which changed them last
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.state.subscriptions.lock().unwrap().clone()
+    }
+
+    // number of accepted connections
+    pub fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+
+    // shared steps not played yet
+    pub fn remaining(&self) -> usize {
+        self.state.steps.lock().unwrap().len()
+    }
+}
+
+impl Step {
+    // frame of typed update, e.g. generated by a test
+    pub fn book(update: &BookUpdate) -> Step {
+        Step::Frame(update.to_string())
+    }
+}
+
+// recorded session: one text frame per line, empty lines skipped
+pub fn recorded(session: &str) -> Vec<Step> {
+    session
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line {
+            "[1010]" => Step::Heartbeat,
+            _ => Step::Frame(line.to_owned()),
+        })
+        .collect()
+}
+
+// load recorded session from file, see recorded
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Step>> {
+    Ok(recorded(&fs::read_to_string(path)?))
+}
+
+// every connection is served by its own task, so several clients may be connected at once
+async fn serve(mut listener: TcpListener, state: Arc<State>) {
+    while let Ok((tcp, _)) = listener.accept().await {
+        state.connections.fetch_add(1, Ordering::SeqCst);
+        state.subscriptions.lock().unwrap().clear();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _ = connection(tcp, &state).await;
+        });
+    }
+}
+
+async fn connection(tcp: TcpStream, state: &State) -> Result<(), Error> {
+    let ws_stream = tokio_tungstenite::accept_async(tcp).await?;
+    let (mut sink, mut stream) = ws_stream.split();
+    let mut replica = state.replica.clone();
+    // channels subscribed on this connection
+    let mut subscriptions = vec![];
+    loop {
+        let step = match replica {
+            Some(ref mut steps) => steps.pop_front(),
+            None => state.steps.lock().unwrap().pop_front(),
+        };
+        match step {
+            Some(Step::Frame(text)) => sink.send(Message::text(text)).await?,
+            Some(Step::Heartbeat) => {
+                sink.send(Message::text(format!("[{}]", HEARTBEAT_CHANNEL)))
+                    .await?
+            }
+            Some(Step::AwaitSubscribe(channel)) => {
+                while !subscriptions.contains(&channel) {
+                    match stream.next().await {
+                        Some(msg) => reply(state, &mut subscriptions, msg?, &mut sink).await?,
+                        None => return Ok(()),
+                    }
+                }
+            }
+            Some(Step::Pause(duration)) => delay_for(duration).await,
+            Some(Step::Disconnect) => return sink.close().await,
+            // script is over, keep answering commands until client leaves
+            None => {
+                while let Some(msg) = stream.next().await {
+                    reply(state, &mut subscriptions, msg?, &mut sink).await?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+// track subscribe/unsubscribe command and acknowledge numeric channels
+async fn reply<S>(
+    state: &State,
+    subscriptions: &mut Vec<String>,
+    msg: Message,
+    sink: &mut S,
+) -> Result<(), Error>
+where
+    S: futures::Sink<Message, Error = Error> + Unpin,
+{
+    let command = match msg {
+        Message::Text(text) => json::parse(&text).unwrap_or(json::JsonValue::Null),
+        _ => return Ok(()),
+    };
+    let channel = match command["channel"].as_str() {
+        Some(channel) => channel.to_owned(),
+        None => return Ok(()),
+    };
+    let subscribed = match command["command"].as_str() {
+        Some("subscribe") => true,
+        Some("unsubscribe") => false,
+        _ => return Ok(()),
+    };
+    subscriptions.retain(|c| *c != channel);
+    if subscribed {
+        subscriptions.push(channel.clone());
+    }
+    *state.subscriptions.lock().unwrap() = subscriptions.clone();
+    if let Ok(id) = channel.parse::<u16>() {
+        sink.send(Message::text(format!("[{},{}]", id, subscribed as u8)))
+            .await?;
+    }
+    Ok(())
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{recorded, MockServer, Step};
+    use crate::actors::{Accountant, Processor};
+    use crate::data::messages::{is_heartbeat, BookRecord, BookUpdate, PushMessage, RecordUpdate};
+    use crate::data::trade::TradeBook;
+    use crate::push::{self, Backoff, Message, PushEvent, ReconnectOptions};
+    use futures::StreamExt;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const SESSION: &str = r#"
+        [189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]
+        [1010]
+        [189,5130996,[["o",1,"0.13169621","0.1"],["t","714116",0,"0.13161901","0.05946471",1504163848]]]
+    "#;
+
+    #[test]
+    fn recorded_session() {
+        let steps = recorded(SESSION);
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[1], Step::Heartbeat);
+    }
+
+    #[tokio::test]
+    async fn replay_typed() {
+        let mut steps = vec![Step::AwaitSubscribe("BTC_BCH".to_owned())];
+        steps.extend(recorded(SESSION));
+        let server = MockServer::start(steps).await.unwrap();
+        let stream = push::subscribe_typed(&server.url(), vec!["BTC_BCH".to_owned()])
+            .await
+            .unwrap();
+        let msgs: Vec<_> = stream.take(3).collect().await;
+        match &msgs[..] {
+            [Ok(PushMessage::Book(initial)), Ok(PushMessage::Heartbeat), Ok(PushMessage::Book(update))] =>
+            {
+                assert!(matches!(initial.records[0], RecordUpdate::Initial(_)));
+                assert_eq!(update.records.len(), 2);
+            }
+            msgs => panic!("unexpected replay {:?}", msgs),
+        }
+        assert_eq!(server.subscriptions(), vec!["BTC_BCH".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn replay_generated() {
+        let update = BookUpdate {
+            book_id: 189,
+            record_id: 5130996,
+            records: vec![RecordUpdate::BuyTotal(BookRecord {
+                rate: "0.13169621".to_owned(),
+                amount: 0.1,
+                epoch_ms: None,
+            })],
+        };
+        let steps = vec![
+            Step::AwaitSubscribe("BTC_BCH".to_owned()),
+            Step::book(&update),
+        ];
+        let server = MockServer::start(steps).await.unwrap();
+        let mut stream = push::subscribe_typed(&server.url(), vec!["BTC_BCH".to_owned()])
+            .await
+            .unwrap();
+        match stream.next().await {
+            Some(Ok(PushMessage::Book(received))) => assert_eq!(received, update),
+            msg => panic!("expected generated update {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn acknowledge_numeric_channel() {
+        let server = MockServer::start(vec![]).await.unwrap();
+        let wait = Duration::from_secs(5);
+        let confirmed =
+            push::subscribe_confirmed(&server.url(), vec!["1002".to_owned()], None, wait).await;
+        assert!(confirmed.is_ok());
+    }
+
+    #[tokio::test]
+    async fn accountant_end_to_end() {
+        let mut steps = vec![Step::AwaitSubscribe("BTC_BCH".to_owned())];
+        steps.extend(recorded(SESSION));
+        steps.push(Step::Disconnect);
+        let server = MockServer::start(steps).await.unwrap();
+        let mut stream = push::subscribe(&server.url(), vec!["BTC_BCH".to_owned()])
+            .await
+            .unwrap();
+        let tb = Arc::new(Mutex::new(TradeBook::new()));
+        let mut accountant = Accountant::new(tb.clone());
+        while let Some(Ok(Message::Text(text))) = stream.next().await {
+            if !is_heartbeat(&text) {
+                accountant.process_message(text).unwrap();
+            }
+        }
+        let mut tb = tb.lock().unwrap();
+        let book = tb.book_by_id(189).unwrap().book_ref();
+        assert_eq!(book.buy["0.13169621"], 0.1);
+        assert_eq!(book.deals.data.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn redundant_concurrent_legs() {
+        let mut steps = vec![Step::AwaitSubscribe("BTC_BCH".to_owned())];
+        steps.extend(recorded(SESSION));
+        let server = MockServer::replicated(steps).await.unwrap();
+        let url = server.url();
+        let mut merged = push::subscribe_redundant(&[&url, &url], vec!["BTC_BCH".to_owned()])
+            .await
+            .unwrap();
+        let mut ids = vec![];
+        while ids.len() < 2 {
+            match merged.next().await {
+                Some(Ok(PushMessage::Book(update))) => ids.push(update.record_id),
+                Some(Ok(_)) => continue,
+                msg => panic!("unexpected merged message {:?}", msg),
+            }
+        }
+        assert_eq!(ids, vec![5130995, 5130996]);
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn reconnect_resync() {
+        let mut steps = vec![Step::AwaitSubscribe("BTC_BCH".to_owned())];
+        steps.extend(recorded(SESSION));
+        steps.push(Step::Disconnect);
+        steps.push(Step::AwaitSubscribe("BTC_BCH".to_owned()));
+        steps.extend(recorded(SESSION));
+        let server = MockServer::start(steps).await.unwrap();
+        let options = ReconnectOptions {
+            backoff: Backoff {
+                initial: Duration::from_millis(10),
+                ..Backoff::default()
+            },
+            ..ReconnectOptions::default()
+        };
+        let mut feed =
+            push::subscribe_reconnecting(&server.url(), vec!["BTC_BCH".to_owned()], options);
+        let (mut books, mut disconnects, mut resynced) = (0, 0, vec![]);
+        while books < 4 {
+            match feed.next().await.unwrap() {
+                PushEvent::Message(Ok(PushMessage::Book(_))) => books += 1,
+                PushEvent::Message(Ok(_)) => continue,
+                PushEvent::Message(Err(err)) => panic!("failed to decode replay {:?}", err),
+                PushEvent::Disconnected(_) => {
+                    disconnects += 1;
+                    continue;
+                }
+                PushEvent::Resynced(channels) => {
+                    resynced = channels;
+                    continue;
+                }
+                PushEvent::Stale | PushEvent::ConnectFailed(_) => continue,
+            }
+        }
+        assert_eq!(disconnects, 1);
+        assert_eq!(resynced, vec!["BTC_BCH".to_owned()]);
+        assert_eq!(server.connections(), 2);
+        assert_eq!(server.remaining(), 0);
+    }
+}
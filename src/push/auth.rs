@@ -0,0 +1,121 @@
+use super::Message;
+use crate::data::messages::ACCOUNT_CHANNEL;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use time;
+
+// nonce should grow with every signed request made with the same key
+static LAST_NONCE: AtomicU64 = AtomicU64::new(0);
+
+// api key and secret to subscribe account notifications channel
+#[derive(Clone)]
+pub struct Credentials {
+    key: String,
+    secret: String,
+}
+
+impl Credentials {
+    pub fn new(key: &str, secret: &str) -> Credentials {
+        Credentials {
+            key: key.to_owned(),
+            secret: secret.to_owned(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    // hex encoded HMAC-SHA512 of payload signed with secret
+    pub fn sign(&self, payload: &str) -> String {
+        let mut mac = Hmac::<Sha512>::new_varkey(self.secret.as_bytes())
+            .expect("HMAC accepts key of any length");
+        mac.input(payload.as_bytes());
+        hex::encode(mac.result().code())
+    }
+}
+
+// keep keys out of logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("key", &"<hidden>")
+            .field("secret", &"<hidden>")
+            .finish()
+    }
+}
+
+// signed subscribe command for account notifications channel
+pub fn message_subscribe_account(credentials: &Credentials) -> Message {
+    message_subscribe_account_nonce(credentials, next_nonce())
+}
+
+fn message_subscribe_account_nonce(credentials: &Credentials, nonce: u64) -> Message {
+    let payload = format!("nonce={}", nonce);
+    Message::text(format!(
+        "{{ \"command\": \"subscribe\", \"channel\": \"{}\", \"key\": \"{}\", \"payload\": \"{}\", \"sign\": \"{}\" }}",
+        ACCOUNT_CHANNEL,
+        credentials.key,
+        payload,
+        credentials.sign(&payload)
+    ))
+}
+
+// milliseconds since epoch, bumped if clock did not move since previous nonce
+fn next_nonce() -> u64 {
+    let now = time::get_time();
+    let now = now.sec as u64 * 1000 + now.nsec as u64 / 1_000_000;
+    let mut last = LAST_NONCE.load(Ordering::SeqCst);
+    loop {
+        let nonce = now.max(last + 1);
+        match LAST_NONCE.compare_exchange(last, nonce, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return nonce,
+            Err(actual) => last = actual,
+        }
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{message_subscribe_account_nonce, next_nonce, Credentials};
+
+    #[test]
+    fn sign_payload() {
+        let credentials = Credentials::new("key", "secret");
+        assert_eq!(
+            credentials.sign("nonce=1"),
+            "1dd023409b0c71a72d21abd20de62d120c6d51234b741a6ce5e13710738e45edf4e29bb291dfe4aadd3cd256ab48b91f68a203aed12a071b5a8b3b2cc8aada67"
+        );
+    }
+
+    #[test]
+    fn debug_hides_credentials() {
+        let credentials = Credentials::new("my-api-key", "my-api-secret");
+        let debug = format!("{:?}", credentials);
+        assert!(!debug.contains("my-api-key"));
+        assert!(!debug.contains("my-api-secret"));
+    }
+
+    #[test]
+    fn subscribe_account_message() {
+        let credentials = Credentials::new("key", "secret");
+        let msg = message_subscribe_account_nonce(&credentials, 1);
+        let msg = json::parse(msg.to_text().unwrap()).unwrap();
+        assert_eq!(msg["channel"], "1000");
+        assert_eq!(msg["key"], "key");
+        assert_eq!(msg["payload"], "nonce=1");
+        assert_eq!(msg["sign"], credentials.sign("nonce=1"));
+    }
+
+    #[test]
+    fn nonce_grows() {
+        let first = next_nonce();
+        assert!(next_nonce() > first);
+    }
+}
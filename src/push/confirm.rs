@@ -0,0 +1,232 @@
+use super::{decode, subscribe_with_credentials, Credentials, Message, WsStream};
+use crate::data::book::TradePairs;
+use crate::data::messages::{Ack, PushMessage, RecordUpdate};
+use crate::error::PoloError;
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tungstenite::error::Error;
+
+// subscribe commands waiting for server reply, poloniex replies in order of commands:
+// numeric channels with ack [1002,1], book channels with initial snapshot,
+// rejected channels with {"error":"Invalid channel."}
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    // channel along with the time its subscribe command was sent
+    channels: VecDeque<(String, Instant)>,
+}
+
+// stream replaying messages received while waiting for confirmations
+pub struct Confirmed<S> {
+    stream: S,
+    backlog: VecDeque<Message>,
+}
+
+impl Pending {
+    pub fn new(channels: &[String]) -> Pending {
+        let now = Instant::now();
+        Pending {
+            channels: channels.iter().map(|c| (c.clone(), now)).collect(),
+        }
+    }
+
+    pub fn push(&mut self, channel: String) {
+        self.channels.push_back((channel, Instant::now()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    // stop waiting for channels subscribed longer than age ago, ex: pair channels which
+    // first frame is not initial book are never confirmed, returns expired channels
+    pub fn expire(&mut self, age: Duration) -> Vec<String> {
+        let (expired, waiting) = self
+            .channels
+            .drain(..)
+            .partition(|(_, sent)| sent.elapsed() >= age);
+        self.channels = waiting;
+        expired.into_iter().map(|(channel, _)| channel).collect()
+    }
+
+    // channels still waiting for reply in order of commands
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.iter().map(|(c, _)| c.clone()).collect()
+    }
+
+    // match server reply to subscribe command, returns:
+    //  - Ok(Some(channel)) - when channel subscription confirmed
+    //  - Ok(None) - if message is not a reply to pending subscription
+    //  - Err(PoloError::Subscription) - when the oldest pending subscription was rejected,
+    //    replies come in order of commands so error answers the first unanswered one
+    pub fn reply(&mut self, msg: &PushMessage) -> Result<Option<String>, PoloError> {
+        let idx = match msg {
+            PushMessage::Ack(Ack {
+                channel,
+                subscribed: true,
+            }) => self.position(|c| c.parse::<u16>() == Ok(*channel)),
+            PushMessage::Book(update) => update
+                .records
+                .iter()
+                .find_map(|rec| match rec {
+                    RecordUpdate::Initial(book) => Some(&book.pair),
+                    _ => None,
+                })
+                .and_then(|pair| {
+                    self.position(|c| {
+                        c.parse::<u16>() == Ok(update.book_id)
+                            || TradePairs::from_str(c).ok().as_ref() == Some(pair)
+                    })
+                }),
+            PushMessage::Error(error) => match self.channels.pop_front() {
+                Some((channel, _)) => return Err(PoloError::Subscription(channel, error.clone())),
+                None => None,
+            },
+            _ => None,
+        };
+        Ok(idx
+            .and_then(|idx| self.channels.remove(idx))
+            .map(|(channel, _)| channel))
+    }
+
+    fn position<P: Fn(&String) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.channels.iter().position(|(c, _)| predicate(c))
+    }
+}
+
+impl<S> Confirmed<S> {
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+}
+
+impl<S> Stream for Confirmed<S>
+where
+    S: Stream<Item = Result<Message, Error>> + Unpin,
+{
+    type Item = Result<Message, Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if let Some(msg) = self.backlog.pop_front() {
+            return Poll::Ready(Some(Ok(msg)));
+        }
+        self.stream.poll_next_unpin(cx)
+    }
+}
+
+// subscribe to channels and wait until server confirms every one of them,
+// rejected channel or no confirmation within wait returns PoloError::Subscription
+pub async fn subscribe_confirmed(
+    connect_addr: &str,
+    pairs: Vec<String>,
+    credentials: Option<&Credentials>,
+    wait: Duration,
+) -> Result<Confirmed<WsStream>, PoloError> {
+    let mut pending = Pending::new(&pairs);
+    let mut stream = subscribe_with_credentials(connect_addr, pairs, credentials).await?;
+    let mut backlog = VecDeque::new();
+    let confirm = async {
+        while !pending.is_empty() {
+            let msg = stream.next().await.ok_or(Error::ConnectionClosed)??;
+            if let Some(Ok(reply)) = decode(msg.clone()) {
+                pending.reply(&reply)?;
+            }
+            backlog.push_back(msg);
+        }
+        Ok(())
+    };
+    match timeout(wait, confirm).await {
+        Ok(Ok(())) => Ok(Confirmed { stream, backlog }),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(PoloError::Subscription(
+            pending.channels().join(","),
+            "no confirmation received".to_owned(),
+        )),
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::Pending;
+    use crate::data::messages::PushMessage;
+    use crate::error::PoloError;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn pending() -> Pending {
+        Pending::new(&[
+            "1002".to_owned(),
+            "BTC_BCH".to_owned(),
+            "USDT_FOO".to_owned(),
+        ])
+    }
+
+    #[test]
+    fn confirm_ack() {
+        let mut pending = pending();
+        let ack = PushMessage::from_str("[1002,1]").unwrap();
+        assert_eq!(pending.reply(&ack).unwrap(), Some("1002".to_owned()));
+        assert_eq!(pending.reply(&ack).unwrap(), None);
+    }
+
+    #[test]
+    fn confirm_initial() {
+        let mut pending = pending();
+        let initial = PushMessage::from_str(r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#).unwrap();
+        let update =
+            PushMessage::from_str(r#"[189,5130996,[["o",1,"0.12906425","0.02691207"]]]"#).unwrap();
+        assert_eq!(pending.reply(&update).unwrap(), None);
+        assert_eq!(pending.reply(&initial).unwrap(), Some("BTC_BCH".to_owned()));
+    }
+
+    #[test]
+    fn reject_in_order() {
+        let mut pending = pending();
+        let ack = PushMessage::from_str("[1002,1]").unwrap();
+        let initial = PushMessage::from_str(r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#).unwrap();
+        let error = PushMessage::from_str(r#"{"error":"Invalid channel."}"#).unwrap();
+        pending.reply(&ack).unwrap();
+        pending.reply(&initial).unwrap();
+        match pending.reply(&error) {
+            Err(PoloError::Subscription(channel, error)) => {
+                assert_eq!(
+                    (channel.as_str(), error.as_str()),
+                    ("USDT_FOO", "Invalid channel.")
+                )
+            }
+            reply => panic!("expected subscription error {:?}", reply),
+        }
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn reject_first_of_several() {
+        let mut pending = Pending::new(&["USDT_FOO".to_owned(), "BTC_BCH".to_owned()]);
+        let error = PushMessage::from_str(r#"{"error":"Invalid channel."}"#).unwrap();
+        match pending.reply(&error) {
+            Err(PoloError::Subscription(channel, _)) => assert_eq!(channel, "USDT_FOO"),
+            reply => panic!("expected subscription error {:?}", reply),
+        }
+        assert_eq!(pending.channels(), vec!["BTC_BCH"]);
+        assert_eq!(pending.reply(&PushMessage::Heartbeat).unwrap(), None);
+    }
+
+    #[test]
+    fn expire_unconfirmed() {
+        let mut pending = pending();
+        assert!(pending.expire(Duration::from_secs(60)).is_empty());
+        assert_eq!(pending.expire(Duration::from_secs(0)).len(), 3);
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use super::proxy::{tunnel, Proxy};
+use super::tls::{connect_tls, TlsConfig};
+use super::WsStream;
+use tokio::net::TcpStream;
+use tokio_tungstenite::client_async_tls;
+use tungstenite::error::Error;
+use url;
+
+// how websocket connection is established
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOptions {
+    // connect directly when None
+    pub proxy: Option<Proxy>,
+    pub tls: TlsConfig,
+}
+
+// open websocket connection to connect_addr honouring options
+pub(crate) async fn connect(
+    connect_addr: &str,
+    options: &ConnectOptions,
+) -> Result<WsStream, Error> {
+    let url = url::Url::parse(connect_addr).map_err(|err| Error::Url(err.to_string().into()))?;
+    let secure = match url.scheme() {
+        "wss" => true,
+        "ws" if !options.tls.require_tls => false,
+        "ws" => return Err(Error::Url("plain ws:// is refused by TLS settings".into())),
+        scheme => {
+            return Err(Error::Url(
+                format!("unsupported url scheme {}", scheme).into(),
+            ))
+        }
+    };
+    // ipv6 address without brackets of url notation, ex: ::1
+    let host = match url.host() {
+        Some(url::Host::Ipv6(addr)) => addr.to_string(),
+        Some(host) => host.to_string(),
+        None => return Err(Error::Url("no host name in the url".into())),
+    };
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| Error::Url("no port in the url".into()))?;
+    let stream = match options.proxy {
+        Some(ref proxy) => tunnel(proxy, &host, port).await?,
+        None => TcpStream::connect((host.as_str(), port)).await?,
+    };
+    if secure && options.tls.is_custom() {
+        return connect_tls(url, &host, stream, &options.tls).await;
+    }
+    Ok(client_async_tls(url, stream).await?.0)
+}
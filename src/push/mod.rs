@@ -0,0 +1,211 @@
+pub use tungstenite::protocol::Message;
+use crate::data::messages::{PushMessage, ACCOUNT_CHANNEL};
+use crate::error::PoloError;
+use crate::get_time;
+use tungstenite::error::Error;
+use futures::{future, SinkExt, Stream, StreamExt};
+use std::str::FromStr;
+use time::Timespec;
+use tokio_tungstenite::{WebSocketStream, MaybeTlsStream};
+
+mod auth;
+mod confirm;
+mod connect;
+mod proxy;
+mod reconnect;
+mod redundant;
+mod shard;
+mod subscription;
+mod tls;
+mod v3;
+
+pub use auth::{message_subscribe_account, Credentials};
+pub use confirm::{subscribe_confirmed, Confirmed};
+pub use connect::ConnectOptions;
+pub use proxy::{Proxy, ProxyAuth};
+pub use reconnect::{
+    subscribe_reconnecting, Backoff, ConnectionHealth, Feed, PushEvent, ReconnectOptions, Watchdog,
+};
+pub use redundant::{subscribe_redundant, Dedup, LegStats, Redundant};
+pub use shard::{subscribe_sharded, ShardOptions, ShardedFeed};
+pub use subscription::Subscription;
+pub use tls::{Certificate, Identity, TlsConfig};
+pub use v3::{
+    decode_v3, message_ping_v3, message_subscribe_v3, message_unsubscribe_v3, subscribe_v3,
+    typed_v3,
+};
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+// subscribe to trading pair ticker updates
+pub async fn subscribe(
+    connect_addr: &str,
+    pairs: Vec<String>,
+) -> Result<WsStream, Error>
+ {
+    subscribe_with_credentials(connect_addr, pairs, None).await
+}
+
+// subscribe to channels signing account notifications channel subscription with credentials
+pub async fn subscribe_with_credentials(
+    connect_addr: &str,
+    pairs: Vec<String>,
+    credentials: Option<&Credentials>,
+) -> Result<WsStream, Error> {
+    subscribe_with_options(connect_addr, pairs, credentials, &ConnectOptions::default()).await
+}
+
+// subscribe to channels connecting as set in options, e.g. through proxy
+pub async fn subscribe_with_options(
+    connect_addr: &str,
+    pairs: Vec<String>,
+    credentials: Option<&Credentials>,
+    options: &ConnectOptions,
+) -> Result<WsStream, Error> {
+    let mut ws_stream = connect::connect(connect_addr, options).await?;
+    for pair in pairs.iter() {
+        let msg = message_subscribe_channel(pair, credentials);
+        ws_stream.send(msg).await?;
+    }
+    Ok(ws_stream)
+}
+
+// subscribe to trading pair ticker updates receiving parsed messages
+pub async fn subscribe_typed(
+    connect_addr: &str,
+    pairs: Vec<String>,
+) -> Result<impl Stream<Item = Result<PushMessage, PoloError>>, PoloError> {
+    let ws_stream = subscribe(connect_addr, pairs).await?;
+    Ok(typed(ws_stream))
+}
+
+// message with local time it was received at
+#[derive(Debug)]
+pub struct Stamped<T> {
+    pub received: Timespec,
+    pub msg: T,
+}
+
+// subscribe to trading pair ticker updates receiving parsed messages with receive time
+pub async fn subscribe_stamped(
+    connect_addr: &str,
+    pairs: Vec<String>,
+) -> Result<impl Stream<Item = Stamped<Result<PushMessage, PoloError>>>, PoloError> {
+    let ws_stream = subscribe(connect_addr, pairs).await?;
+    Ok(stamped(ws_stream))
+}
+
+// parse websocket frames of the stream skipping control frames
+pub fn typed<S>(stream: S) -> impl Stream<Item = Result<PushMessage, PoloError>>
+where
+    S: Stream<Item = Result<Message, Error>>,
+{
+    stamped(stream).map(|stamped| stamped.msg)
+}
+
+// parse websocket frames stamping them with local time before parsing
+pub fn stamped<S>(stream: S) -> impl Stream<Item = Stamped<Result<PushMessage, PoloError>>>
+where
+    S: Stream<Item = Result<Message, Error>>,
+{
+    stream.filter_map(|msg| {
+        let received = get_time();
+        future::ready(
+            match msg {
+                Ok(msg) => decode(msg),
+                Err(err) => Some(Err(PoloError::from(err))),
+            }
+            .map(|msg| Stamped { received, msg }),
+        )
+    })
+}
+
+// parse text frame into PushMessage, on failure raw text is kept in PoloError::Decode
+// returns None for ping, pong, binary and close frames
+pub fn decode(msg: Message) -> Option<Result<PushMessage, PoloError>> {
+    match msg {
+        Message::Text(text) => Some(
+            PushMessage::from_str(&text).map_err(|err| PoloError::Decode(Box::new(err), text)),
+        ),
+        _ => None,
+    }
+}
+
+fn message_subscribe_channel(channel: &str, credentials: Option<&Credentials>) -> Message {
+    match credentials {
+        Some(credentials) if channel.parse() == Ok(ACCOUNT_CHANNEL) => {
+            message_subscribe_account(credentials)
+        }
+        _ => message_subscribe(channel),
+    }
+}
+
+fn message_subscribe(channel: &str) -> Message {
+    Message::text(format!(
+        "{{ \"command\": \"subscribe\", \"channel\": \"{}\" }}",
+        channel
+    ))
+}
+
+fn message_unsubscribe(channel: &str) -> Message {
+    Message::text(format!(
+        "{{ \"command\": \"unsubscribe\", \"channel\": \"{}\" }}",
+        channel
+    ))
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, message_subscribe_channel, stamped, Credentials, Message};
+    use crate::data::messages::PushMessage;
+    use crate::error::PoloError;
+    use crate::get_time;
+    use futures::executor::block_on;
+    use futures::{stream, StreamExt};
+
+    #[test]
+    fn decode_text() {
+        match decode(Message::text("[1010]")) {
+            Some(Ok(PushMessage::Heartbeat)) => (),
+            msg => panic!("expected heartbeat {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn decode_keeps_raw() {
+        match decode(Message::text("[189,4811424]]")) {
+            Some(Err(PoloError::Decode(_, raw))) => assert_eq!(raw, "[189,4811424]]"),
+            msg => panic!("expected decode error {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn decode_skips_control() {
+        assert!(decode(Message::Ping(vec![1])).is_none());
+    }
+
+    #[test]
+    fn stamp_received() {
+        let before = get_time();
+        let frames = vec![Ok(Message::text("[1010]")), Ok(Message::Pong(vec![]))];
+        let msgs: Vec<_> = block_on(stamped(stream::iter(frames)).collect());
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].received >= before);
+        assert!(msgs[0].received <= get_time());
+    }
+
+    #[test]
+    fn subscribe_account_signed() {
+        let credentials = Credentials::new("key", "secret");
+        let msg = message_subscribe_channel("1000", Some(&credentials));
+        assert!(msg.to_text().unwrap().contains("\"sign\""));
+        let msg = message_subscribe_channel("1000", None);
+        assert!(!msg.to_text().unwrap().contains("\"sign\""));
+        let msg = message_subscribe_channel("USDT_BTC", Some(&credentials));
+        assert!(!msg.to_text().unwrap().contains("\"sign\""));
+    }
+}
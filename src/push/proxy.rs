@@ -0,0 +1,436 @@
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// proxy to tunnel websocket connection through, addr is "host:port" of the proxy
+#[derive(Clone, Debug, PartialEq)]
+pub enum Proxy {
+    // HTTP CONNECT, auth is sent as Proxy-Authorization: Basic
+    Http {
+        addr: String,
+        auth: Option<ProxyAuth>,
+    },
+    // SOCKS5 with target host resolved by proxy, auth is RFC 1929 username/password
+    Socks5 {
+        addr: String,
+        auth: Option<ProxyAuth>,
+    },
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_NO_AUTH: u8 = 0;
+const SOCKS_PASSWORD_AUTH: u8 = 2;
+// version of username/password subnegotiation, RFC 1929
+const SOCKS_PASSWORD_AUTH_VERSION: u8 = 1;
+const SOCKS_CONNECT: u8 = 1;
+const SOCKS_IPV4: u8 = 1;
+const SOCKS_DOMAIN: u8 = 3;
+const SOCKS_IPV6: u8 = 4;
+// limit of HTTP CONNECT response head, proxies reply with a few short headers
+const MAX_RESPONSE_HEAD: usize = 8192;
+
+// keep passwords out of logs
+impl fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
+impl Proxy {
+    pub fn addr(&self) -> &str {
+        match self {
+            Proxy::Http { addr, .. } | Proxy::Socks5 { addr, .. } => addr,
+        }
+    }
+}
+
+// connect to proxy and ask it to open tunnel to host:port, ipv6 host is given without brackets
+pub(crate) async fn tunnel(proxy: &Proxy, host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy.addr()).await?;
+    match proxy {
+        Proxy::Http { auth, .. } => http_connect(&mut stream, host, port, auth.as_ref()).await?,
+        Proxy::Socks5 { auth, .. } => {
+            socks5_connect(&mut stream, host, port, auth.as_ref()).await?
+        }
+    }
+    Ok(stream)
+}
+
+async fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&ProxyAuth>,
+) -> io::Result<()> {
+    stream
+        .write_all(http_connect_request(host, port, auth).as_bytes())
+        .await?;
+    // read byte by byte so nothing of websocket handshake is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > MAX_RESPONSE_HEAD {
+            return Err(proxy_error("proxy response is too long"));
+        }
+        head.push(stream.read_u8().await?);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status = head.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(proxy_error(&format!("proxy refused tunnel: {}", status))),
+    }
+}
+
+fn http_connect_request(host: &str, port: u16, auth: Option<&ProxyAuth>) -> String {
+    // ipv6 address is bracketed in authority, ex: [::1]:443
+    let authority = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(addr)) => format!("[{}]:{}", addr, port),
+        _ => format!("{}:{}", host, port),
+    };
+    let mut request = format!(
+        "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n",
+        authority = authority
+    );
+    if let Some(auth) = auth {
+        let credentials = base64::encode(format!("{}:{}", auth.username, auth.password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&ProxyAuth>,
+) -> io::Result<()> {
+    let method = match auth {
+        Some(_) => SOCKS_PASSWORD_AUTH,
+        None => SOCKS_NO_AUTH,
+    };
+    stream.write_all(&[SOCKS_VERSION, 1, method]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION || reply[1] != method {
+        return Err(proxy_error("proxy does not accept authentication method"));
+    }
+    if let Some(auth) = auth {
+        stream.write_all(&socks5_auth_request(auth)?).await?;
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_PASSWORD_AUTH_VERSION {
+            return Err(proxy_error(
+                "proxy replied with unknown authentication version",
+            ));
+        }
+        if reply[1] != 0 {
+            return Err(proxy_error("proxy rejected username or password"));
+        }
+    }
+    stream
+        .write_all(&socks5_connect_request(host, port)?)
+        .await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(proxy_error("proxy replied to connect with unknown version"));
+    }
+    if reply[1] != 0 {
+        return Err(proxy_error(&format!(
+            "proxy failed to connect, reply {}",
+            reply[1]
+        )));
+    }
+    // skip address proxy bound to
+    let len = match reply[3] {
+        SOCKS_IPV4 => 4,
+        SOCKS_IPV6 => 16,
+        SOCKS_DOMAIN => stream.read_u8().await? as usize,
+        _ => return Err(proxy_error("proxy replied with unknown address type")),
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+fn socks5_auth_request(auth: &ProxyAuth) -> io::Result<Vec<u8>> {
+    let mut request = vec![SOCKS_PASSWORD_AUTH_VERSION];
+    for field in &[&auth.username, &auth.password] {
+        request.push(short_len(field, "proxy username and password")?);
+        request.extend_from_slice(field.as_bytes());
+    }
+    Ok(request)
+}
+
+// ip addresses are sent as such, host names are resolved by proxy
+fn socks5_connect_request(host: &str, port: u16) -> io::Result<Vec<u8>> {
+    let mut request = vec![SOCKS_VERSION, SOCKS_CONNECT, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(addr)) => {
+            request.push(SOCKS_IPV4);
+            request.extend_from_slice(&addr.octets());
+        }
+        Ok(IpAddr::V6(addr)) => {
+            request.push(SOCKS_IPV6);
+            request.extend_from_slice(&addr.octets());
+        }
+        Err(_) => {
+            request.push(SOCKS_DOMAIN);
+            request.push(short_len(host, "host name")?);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+// socks fields are prefixed with one byte length
+fn short_len(field: &str, title: &str) -> io::Result<u8> {
+    if field.len() > u8::MAX as usize {
+        return Err(proxy_error(&format!(
+            "{} should be shorter than 256 bytes",
+            title
+        )));
+    }
+    Ok(field.len() as u8)
+}
+
+fn proxy_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, msg)
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        http_connect_request, socks5_auth_request, socks5_connect_request, Proxy, ProxyAuth,
+    };
+    use crate::mock::{MockServer, Step};
+    use crate::push::{self, ConnectOptions, Message};
+    use futures::{future, StreamExt};
+    use std::net::IpAddr;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tungstenite::error::Error;
+
+    fn auth() -> ProxyAuth {
+        ProxyAuth {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        }
+    }
+
+    #[test]
+    fn http_request() {
+        assert_eq!(
+            http_connect_request("api2.poloniex.com", 443, Some(&auth())),
+            "CONNECT api2.poloniex.com:443 HTTP/1.1\r\nHost: api2.poloniex.com:443\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+        );
+        assert_eq!(
+            http_connect_request("::1", 443, None),
+            "CONNECT [::1]:443 HTTP/1.1\r\nHost: [::1]:443\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn socks5_requests() {
+        assert_eq!(
+            socks5_auth_request(&auth()).unwrap(),
+            b"\x01\x04user\x04pass".to_vec()
+        );
+        assert_eq!(
+            socks5_connect_request("host", 443).unwrap(),
+            b"\x05\x01\x00\x03\x04host\x01\xbb".to_vec()
+        );
+        assert_eq!(
+            socks5_connect_request("127.0.0.1", 443).unwrap(),
+            b"\x05\x01\x00\x01\x7f\x00\x00\x01\x01\xbb".to_vec()
+        );
+        let mut ipv6 = b"\x05\x01\x00\x04".to_vec();
+        ipv6.extend_from_slice(&[0; 15]);
+        ipv6.extend_from_slice(b"\x01\x01\xbb");
+        assert_eq!(socks5_connect_request("::1", 443).unwrap(), ipv6);
+        assert!(socks5_connect_request(&"h".repeat(256), 443).is_err());
+    }
+
+    #[test]
+    fn debug_hides_password() {
+        assert!(!format!("{:?}", auth()).contains("\"pass\""));
+    }
+
+    // stand-in proxy serving one tunnel to target after handshake
+    async fn proxy<H, F>(handshake: H) -> String
+    where
+        H: FnOnce(TcpStream) -> F + Send + 'static,
+        F: future::Future<Output = Option<(TcpStream, String)>> + Send,
+    {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (inbound, _) = listener.accept().await.unwrap();
+            if let Some((mut inbound, target)) = handshake(inbound).await {
+                let mut outbound = TcpStream::connect(target).await.unwrap();
+                let (mut ri, mut wi) = inbound.split();
+                let (mut ro, mut wo) = outbound.split();
+                let _ =
+                    future::try_join(io::copy(&mut ri, &mut wo), io::copy(&mut ro, &mut wi)).await;
+            }
+        });
+        addr
+    }
+
+    async fn http_handshake(mut stream: TcpStream) -> Option<(TcpStream, String)> {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        if !head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n") {
+            stream
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+            return None;
+        }
+        let target = head.split_whitespace().nth(1).unwrap().to_owned();
+        stream
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        Some((stream, target))
+    }
+
+    async fn socks5_handshake(stream: TcpStream) -> Option<(TcpStream, String)> {
+        socks5_handshake_replying(stream, [1, 0], 5).await
+    }
+
+    async fn socks5_handshake_replying(
+        mut stream: TcpStream,
+        auth_reply: [u8; 2],
+        connect_version: u8,
+    ) -> Option<(TcpStream, String)> {
+        let mut buf = [0u8; 3];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [5, 1, 2]);
+        stream.write_all(&[5, 2]).await.unwrap();
+        let mut auth = [0u8; 11];
+        stream.read_exact(&mut auth).await.unwrap();
+        assert_eq!(&auth, b"\x01\x04user\x04pass");
+        stream.write_all(&auth_reply).await.unwrap();
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request).await.ok()?;
+        let host = match request[3] {
+            1 => {
+                let mut ip = [0u8; 4];
+                stream.read_exact(&mut ip).await.unwrap();
+                IpAddr::from(ip).to_string()
+            }
+            _ => {
+                let mut host = vec![0u8; stream.read_u8().await.unwrap() as usize];
+                stream.read_exact(&mut host).await.unwrap();
+                String::from_utf8(host).unwrap()
+            }
+        };
+        let port = stream.read_u16().await.unwrap();
+        stream
+            .write_all(&[connect_version, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+            .await
+            .unwrap();
+        Some((stream, format!("{}:{}", host, port)))
+    }
+
+    async fn heartbeat_through(proxy: Proxy) -> Result<Message, Error> {
+        let server = MockServer::start(vec![
+            Step::AwaitSubscribe("BTC_BCH".to_owned()),
+            Step::Heartbeat,
+        ])
+        .await
+        .unwrap();
+        let options = ConnectOptions {
+            proxy: Some(proxy),
+            ..ConnectOptions::default()
+        };
+        let mut stream =
+            push::subscribe_with_options(&server.url(), vec!["BTC_BCH".to_owned()], None, &options)
+                .await?;
+        stream.next().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn tunnel_http() {
+        let addr = proxy(http_handshake).await;
+        let msg = heartbeat_through(Proxy::Http {
+            addr,
+            auth: Some(auth()),
+        })
+        .await
+        .unwrap();
+        assert_eq!(msg, Message::text("[1010]"));
+    }
+
+    #[tokio::test]
+    async fn tunnel_http_unauthorized() {
+        let addr = proxy(http_handshake).await;
+        let err = heartbeat_through(Proxy::Http { addr, auth: None }).await;
+        match err {
+            Err(Error::Io(err)) => assert!(err.to_string().contains("407")),
+            msg => panic!("tunnel without auth {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn tunnel_socks5() {
+        let addr = proxy(socks5_handshake).await;
+        let msg = heartbeat_through(Proxy::Socks5 {
+            addr,
+            auth: Some(auth()),
+        })
+        .await
+        .unwrap();
+        assert_eq!(msg, Message::text("[1010]"));
+    }
+
+    #[tokio::test]
+    async fn socks5_auth_version_checked() {
+        let addr = proxy(|stream| socks5_handshake_replying(stream, [5, 0], 5)).await;
+        let err = heartbeat_through(Proxy::Socks5 {
+            addr,
+            auth: Some(auth()),
+        })
+        .await;
+        match err {
+            Err(Error::Io(err)) => assert!(err.to_string().contains("authentication version")),
+            msg => panic!("socks5 auth reply of wrong version {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn socks5_connect_version_checked() {
+        let addr = proxy(|stream| socks5_handshake_replying(stream, [1, 0], 4)).await;
+        let err = heartbeat_through(Proxy::Socks5 {
+            addr,
+            auth: Some(auth()),
+        })
+        .await;
+        match err {
+            Err(Error::Io(err)) => {
+                assert!(err.to_string().contains("connect with unknown version"))
+            }
+            msg => panic!("socks5 connect reply of wrong version {:?}", msg),
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use super::confirm::Pending;
+use super::subscription::{apply, Command, Subscription};
+use super::{decode, subscribe_with_options, ConnectOptions, Credentials, Message};
+use crate::data::messages::PushMessage;
+use crate::error::PoloError;
+use crate::get_time;
+use futures::channel::mpsc;
+use futures::task::{Context, Poll};
+use futures::{future, FutureExt, SinkExt, Stream, StreamExt};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::Timespec;
+use tokio::time::{delay_for, timeout_at, Instant};
+use tungstenite::error::Error;
+
+const FEED_BUFFER: usize = 1024;
+// subscription without reply within this time is no longer waited for,
+// so a later error is not charged to it
+const PENDING_TIMEOUT: Duration = Duration::from_secs(30);
+
+// exponential reconnect delays: initial * multiplier^attempt capped by max
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: u32,
+}
+
+// connection is considered stale after timeout without any message (heartbeats included)
+#[derive(Clone, Debug)]
+pub struct Watchdog {
+    pub timeout: Duration,
+    // drop stale connection and reconnect, otherwise only flag it
+    pub reconnect: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReconnectOptions {
+    pub backoff: Backoff,
+    pub watchdog: Option<Watchdog>,
+    // required to subscribe account notifications channel
+    pub credentials: Option<Credentials>,
+    pub connection: ConnectOptions,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionHealth {
+    pub last_message: Option<Timespec>,
+    pub last_heartbeat: Option<Timespec>,
+    pub stale: bool,
+}
+
+#[derive(Debug)]
+pub enum PushEvent {
+    Message(Result<PushMessage, PoloError>),
+    // watchdog timeout passed without messages
+    Stale,
+    // connection was closed by server (None) or dropped with error
+    Disconnected(Option<Error>),
+    // connect or subscribe attempt failed, the next one follows after backoff delay
+    ConnectFailed(Error),
+    // connection re-established and channels subscribed again,
+    // books of those channels should be dropped until fresh RecordUpdate::Initial
+    Resynced(Vec<String>),
+}
+
+// stream of push events surviving disconnects, stops the connection task when dropped
+pub struct Feed {
+    events: mpsc::Receiver<PushEvent>,
+    health: Arc<Mutex<ConnectionHealth>>,
+    subscription: Subscription,
+}
+
+enum Input {
+    Frame(Option<Result<Message, Error>>),
+    Command(Option<Command>),
+    Timeout,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(60),
+            multiplier: 2,
+        }
+    }
+}
+
+impl Backoff {
+    // delay before reconnect attempt (counting from 0) without jitter
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.checked_pow(attempt).unwrap_or(u32::MAX);
+        self.initial
+            .checked_mul(factor)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+
+    // randomized delay within [delay / 2, delay] so clients do not reconnect in lockstep
+    pub fn jittered(&self, attempt: u32) -> Duration {
+        let delay = self.delay(attempt);
+        delay / 2 + jitter(delay / 2)
+    }
+}
+
+impl Watchdog {
+    // time the connection turns stale unless a frame is received before
+    fn deadline(&self) -> Instant {
+        Instant::now() + self.timeout
+    }
+}
+
+impl Feed {
+    pub fn health(&self) -> ConnectionHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    // handle to change subscribed channels while feed is running
+    pub fn subscription(&self) -> Subscription {
+        self.subscription.clone()
+    }
+}
+
+impl Stream for Feed {
+    type Item = PushEvent;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<PushEvent>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+// subscribe to channels and keep resubscribing them whenever connection drops
+// should be called within tokio runtime, connection is served by spawned task
+pub fn subscribe_reconnecting(
+    connect_addr: &str,
+    pairs: Vec<String>,
+    options: ReconnectOptions,
+) -> Feed {
+    let (events, rx) = mpsc::channel(FEED_BUFFER);
+    let (commands, commands_rx) = mpsc::unbounded();
+    let health = Arc::new(Mutex::new(ConnectionHealth::default()));
+    let channels = Arc::new(Mutex::new(pairs));
+    tokio::spawn(run(
+        connect_addr.to_owned(),
+        options,
+        events,
+        commands_rx,
+        health.clone(),
+        channels.clone(),
+    ));
+    Feed {
+        events: rx,
+        health,
+        subscription: Subscription::new(commands, channels),
+    }
+}
+
+async fn run(
+    connect_addr: String,
+    options: ReconnectOptions,
+    mut events: mpsc::Sender<PushEvent>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    health: Arc<Mutex<ConnectionHealth>>,
+    channels: Arc<Mutex<Vec<String>>>,
+) {
+    let mut attempt = 0;
+    let mut resync = false;
+    while !events.is_closed() {
+        // commands received while disconnected only change channels to subscribe
+        while let Some(Some(command)) = commands.next().now_or_never() {
+            apply(&channels, command, options.credentials.as_ref());
+        }
+        let pairs = channels.lock().unwrap().clone();
+        let credentials = options.credentials.as_ref();
+        let connected = subscribe_with_options(
+            &connect_addr,
+            pairs.clone(),
+            credentials,
+            &options.connection,
+        )
+        .await;
+        let connected = match connected {
+            Ok(ws_stream) => Some(ws_stream),
+            Err(err) => {
+                if events.send(PushEvent::ConnectFailed(err)).await.is_err() {
+                    return;
+                }
+                None
+            }
+        };
+        if let Some(ws_stream) = connected {
+            attempt = 0;
+            health.lock().unwrap().stale = false;
+            let mut pending = Pending::new(&pairs);
+            if resync && events.send(PushEvent::Resynced(pairs)).await.is_err() {
+                return;
+            }
+            resync = true;
+            let (mut sink, mut stream) = ws_stream.split();
+            // only frames from server postpone the watchdog, local commands do not
+            let mut deadline = options.watchdog.as_ref().map(Watchdog::deadline);
+            let reason = loop {
+                let event = match next_input(&mut stream, &mut commands, deadline).await {
+                    Input::Frame(Some(Ok(Message::Close(_)))) | Input::Frame(None) => break None,
+                    Input::Frame(Some(Err(err))) => break Some(err),
+                    Input::Frame(Some(Ok(msg))) => {
+                        deadline = options.watchdog.as_ref().map(Watchdog::deadline);
+                        match received(&health, msg) {
+                            Some(event) => confirm(&mut pending, &channels, event),
+                            None => continue,
+                        }
+                    }
+                    Input::Command(Some(command)) => {
+                        let channel = match command {
+                            Command::Subscribe(ref channel) => Some(channel.clone()),
+                            Command::Unsubscribe(_) => None,
+                        };
+                        if let Some(msg) = apply(&channels, command, credentials) {
+                            if let Some(channel) = channel {
+                                pending.push(channel);
+                            }
+                            if let Err(err) = sink.send(msg).await {
+                                break Some(err);
+                            }
+                        }
+                        continue;
+                    }
+                    // feed and all subscription handles were dropped
+                    Input::Command(None) => return,
+                    Input::Timeout => {
+                        deadline = options.watchdog.as_ref().map(Watchdog::deadline);
+                        let was_stale = health.lock().unwrap().stale;
+                        health.lock().unwrap().stale = true;
+                        if let Some(Watchdog {
+                            reconnect: true, ..
+                        }) = options.watchdog
+                        {
+                            break Some(Error::Io(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "no messages within watchdog timeout",
+                            )));
+                        }
+                        if was_stale {
+                            continue;
+                        }
+                        PushEvent::Stale
+                    }
+                };
+                if events.send(event).await.is_err() {
+                    return;
+                }
+            };
+            if events.send(PushEvent::Disconnected(reason)).await.is_err() {
+                return;
+            }
+        }
+        delay_for(options.backoff.jittered(attempt)).await;
+        attempt = attempt.saturating_add(1);
+    }
+}
+
+// wait for next websocket frame or subscription command, whichever comes first,
+// Input::Timeout once deadline passes
+async fn next_input<S>(
+    stream: &mut S,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+    deadline: Option<Instant>,
+) -> Input
+where
+    S: Stream<Item = Result<Message, Error>> + Unpin,
+{
+    let frame = stream.next().map(Input::Frame);
+    let command = commands.next().map(Input::Command);
+    let input = future::select(frame, command).map(|either| either.factor_first().0);
+    match deadline {
+        Some(deadline) => timeout_at(deadline, input).await.unwrap_or(Input::Timeout),
+        None => input.await,
+    }
+}
+
+// update connection health with received message, None for control frames
+fn received(health: &Mutex<ConnectionHealth>, msg: Message) -> Option<PushEvent> {
+    let now = get_time();
+    let mut health = health.lock().unwrap();
+    health.last_message = Some(now);
+    health.stale = false;
+    let msg = decode(msg)?;
+    if let Ok(PushMessage::Heartbeat) = msg {
+        health.last_heartbeat = Some(now);
+    }
+    Some(PushEvent::Message(msg))
+}
+
+// match event against pending subscriptions, rejected channel is dropped
+// from active channels so it is not resubscribed after reconnect
+fn confirm(pending: &mut Pending, channels: &Mutex<Vec<String>>, event: PushEvent) -> PushEvent {
+    pending.expire(PENDING_TIMEOUT);
+    match event {
+        PushEvent::Message(Ok(ref msg)) => match pending.reply(msg) {
+            Err(PoloError::Subscription(channel, error)) => {
+                channels.lock().unwrap().retain(|c| *c != channel);
+                PushEvent::Message(Err(PoloError::Subscription(channel, error)))
+            }
+            _ => event,
+        },
+        event => event,
+    }
+}
+
+// cheap pseudo-random duration in [0, max) seeded from clock nanoseconds
+fn jitter(max: Duration) -> Duration {
+    let max = max.as_nanos() as u64;
+    if max == 0 {
+        return Duration::from_nanos(0);
+    }
+    let seed = get_time().nsec as u64;
+    Duration::from_nanos(seed.wrapping_mul(6_364_136_223_846_793_005) % max)
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{confirm, next_input, received, subscribe_reconnecting, Command, Input};
+    use super::{Backoff, ConnectionHealth, Message, Pending, PushEvent, ReconnectOptions};
+    use crate::data::messages::PushMessage;
+    use crate::error::PoloError;
+    use futures::channel::mpsc;
+    use futures::{stream, StreamExt};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::time::{delay_for, timeout, Instant};
+    use tungstenite::error::Error;
+
+    #[test]
+    fn backoff_exponential() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(0), Duration::from_millis(500));
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn backoff_capped() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(10), Duration::from_secs(60));
+        assert_eq!(backoff.delay(1000), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn backoff_jittered() {
+        let backoff = Backoff::default();
+        for attempt in 0..10 {
+            let delay = backoff.jittered(attempt);
+            assert!(delay >= backoff.delay(attempt) / 2);
+            assert!(delay <= backoff.delay(attempt));
+        }
+    }
+
+    #[test]
+    fn health_tracks_heartbeat() {
+        let health = Mutex::new(ConnectionHealth {
+            stale: true,
+            ..ConnectionHealth::default()
+        });
+        match received(&health, Message::text("[1010]")) {
+            Some(PushEvent::Message(Ok(PushMessage::Heartbeat))) => (),
+            event => panic!("heartbeat was not recognised {:?}", event),
+        }
+        let health = health.into_inner().unwrap();
+        assert!(!health.stale);
+        assert!(health.last_heartbeat.is_some());
+        assert_eq!(health.last_heartbeat, health.last_message);
+    }
+
+    #[test]
+    fn health_tracks_message() {
+        let health = Mutex::new(ConnectionHealth::default());
+        match received(&health, Message::text("[1002,1]")) {
+            Some(PushEvent::Message(Ok(PushMessage::Ack(_)))) => (),
+            event => panic!("message was taken for heartbeat {:?}", event),
+        }
+        let health = health.into_inner().unwrap();
+        assert!(health.last_message.is_some());
+        assert!(health.last_heartbeat.is_none());
+    }
+
+    #[test]
+    fn rejected_channel_dropped() {
+        let channels = Mutex::new(vec!["USDT_BTC".to_owned(), "USDT_FOO".to_owned()]);
+        let mut pending = Pending::new(&["USDT_FOO".to_owned()]);
+        let event = received(&health(), Message::text(r#"{"error":"Invalid channel."}"#)).unwrap();
+        match confirm(&mut pending, &channels, event) {
+            PushEvent::Message(Err(PoloError::Subscription(channel, _))) => {
+                assert_eq!(channel, "USDT_FOO")
+            }
+            event => panic!("rejected subscription was not reported {:?}", event),
+        }
+        assert_eq!(channels.into_inner().unwrap(), vec!["USDT_BTC".to_owned()]);
+    }
+
+    #[test]
+    fn rejected_one_of_several_dropped() {
+        let channels = Mutex::new(vec!["BTC_BCH".to_owned(), "USDT_FOO".to_owned()]);
+        let mut pending = Pending::new(&["BTC_BCH".to_owned()]);
+        pending.push("USDT_FOO".to_owned());
+        let initial = r#"[189, 5130995, [["i", {"currencyPair": "BTC_BCH", "orderBook": [{"0.13161901": 0.23709568}, {"0.13169621": 0.2331}]}]]]"#;
+        let event = received(&health(), Message::text(initial)).unwrap();
+        confirm(&mut pending, &channels, event);
+        let error = received(&health(), Message::text(r#"{"error":"Invalid channel."}"#));
+        match confirm(&mut pending, &channels, error.unwrap()) {
+            PushEvent::Message(Err(PoloError::Subscription(channel, _))) => {
+                assert_eq!(channel, "USDT_FOO")
+            }
+            event => panic!("rejected subscription was not reported {:?}", event),
+        }
+        assert!(pending.is_empty());
+        assert_eq!(channels.into_inner().unwrap(), vec!["BTC_BCH".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_reported() {
+        // nothing listens on the port once listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let options = ReconnectOptions {
+            backoff: Backoff {
+                initial: Duration::from_millis(10),
+                ..Backoff::default()
+            },
+            ..ReconnectOptions::default()
+        };
+        let url = format!("ws://{}", addr);
+        let mut feed = subscribe_reconnecting(&url, vec!["BTC_BCH".to_owned()], options);
+        for _ in 0..2 {
+            match feed.next().await {
+                Some(PushEvent::ConnectFailed(_)) => (),
+                event => panic!("failed attempt was not reported {:?}", event),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_do_not_postpone_watchdog() {
+        let (commands, mut commands_rx) = mpsc::unbounded();
+        tokio::spawn(async move {
+            while commands
+                .unbounded_send(Command::Unsubscribe("BTC_BCH".to_owned()))
+                .is_ok()
+            {
+                delay_for(Duration::from_millis(10)).await;
+            }
+        });
+        let mut frames = stream::pending::<Result<Message, Error>>();
+        let deadline = Some(Instant::now() + Duration::from_millis(50));
+        let watchdog = async {
+            loop {
+                if let Input::Timeout = next_input(&mut frames, &mut commands_rx, deadline).await {
+                    break;
+                }
+            }
+        };
+        assert!(timeout(Duration::from_secs(5), watchdog).await.is_ok());
+    }
+
+    fn health() -> Mutex<ConnectionHealth> {
+        Mutex::new(ConnectionHealth::default())
+    }
+}
@@ -0,0 +1,287 @@
+use super::subscribe_typed;
+use crate::data::messages::{BookUpdate, PushMessage, TickerUpdate};
+use crate::error::PoloError;
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::mem;
+use std::pin::Pin;
+use time::Timespec;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LegStats {
+    // book updates forwarded from this leg because it delivered them first
+    pub wins: u64,
+    // book updates dropped because other leg already delivered them
+    pub duplicates: u64,
+}
+
+// record ids remembered per book, older ones are taken for duplicates
+const SEEN_WINDOW: usize = 1024;
+
+// forwards every book update sequence number once per book, whichever leg delivers it first
+#[derive(Debug, Default)]
+pub struct Dedup {
+    // the latest SEEN_WINDOW record ids per book, a leg may deliver ids missing on the other one
+    seen: HashMap<u16, BTreeSet<u64>>,
+    stats: Vec<LegStats>,
+    // last forwarded ticker per pair_id
+    tickers: HashMap<u16, TickerUpdate>,
+    last_volume: Option<Timespec>,
+    // heartbeat was forwarded after the last other message
+    heartbeat_sent: bool,
+}
+
+// same channels subscribed over several connections merged into one stream,
+// see Dedup::accept_message for messages forwarded once
+pub struct Redundant<S> {
+    legs: Vec<S>,
+    dedup: Dedup,
+    // leg polled first next time so fast leg does not hide the other
+    next: usize,
+}
+
+impl Dedup {
+    pub fn new(legs: usize) -> Dedup {
+        Dedup {
+            stats: vec![LegStats::default(); legs],
+            ..Dedup::default()
+        }
+    }
+
+    // true if message should be forwarded:
+    //  - book update not seen yet, see accept
+    //  - heartbeat unless one was already forwarded since the last other message
+    //  - ticker which differs from the last one of the same pair
+    //  - volume update newer than the last one
+    //  - anything else, ex: acks and errors come from every leg
+    pub fn accept_message(&mut self, leg: usize, msg: &PushMessage) -> bool {
+        let accepted = match msg {
+            PushMessage::Book(update) => self.accept(leg, update),
+            PushMessage::Heartbeat => return !mem::replace(&mut self.heartbeat_sent, true),
+            PushMessage::Ticker(ticker) => {
+                let fresh = self.tickers.get(&ticker.pair_id) != Some(ticker);
+                if fresh {
+                    self.tickers.insert(ticker.pair_id, ticker.clone());
+                }
+                fresh
+            }
+            PushMessage::Volume24h(volume) => {
+                let fresh = self.last_volume < Some(volume.time);
+                if fresh {
+                    self.last_volume = Some(volume.time);
+                }
+                fresh
+            }
+            _ => true,
+        };
+        self.heartbeat_sent &= !accepted;
+        accepted
+    }
+
+    // true if update was not seen yet and should be forwarded
+    pub fn accept(&mut self, leg: usize, update: &BookUpdate) -> bool {
+        if self.stats.len() <= leg {
+            self.stats.resize(leg + 1, LegStats::default());
+        }
+        let seen = self.seen.entry(update.book_id).or_default();
+        let oldest = seen.iter().next().copied().unwrap_or(0);
+        let stale = seen.len() >= SEEN_WINDOW && update.record_id < oldest;
+        if stale || !seen.insert(update.record_id) {
+            self.stats[leg].duplicates += 1;
+            return false;
+        }
+        if seen.len() > SEEN_WINDOW {
+            seen.remove(&oldest);
+        }
+        self.stats[leg].wins += 1;
+        true
+    }
+
+    pub fn stats(&self) -> &[LegStats] {
+        &self.stats
+    }
+}
+
+impl<S> Redundant<S> {
+    pub fn new(legs: Vec<S>) -> Redundant<S> {
+        Redundant {
+            dedup: Dedup::new(legs.len()),
+            legs,
+            next: 0,
+        }
+    }
+
+    // win and duplicate counters indexed by leg
+    pub fn stats(&self) -> &[LegStats] {
+        self.dedup.stats()
+    }
+}
+
+impl<S> Stream for Redundant<S>
+where
+    S: Stream<Item = Result<PushMessage, PoloError>> + Unpin,
+{
+    type Item = Result<PushMessage, PoloError>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let count = this.legs.len();
+        let mut closed = 0;
+        let mut i = 0;
+        while i < count {
+            let leg = (this.next + i) % count;
+            match this.legs[leg].poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(msg))) => {
+                    if this.dedup.accept_message(leg, &msg) {
+                        this.next = (leg + 1) % count;
+                        return Poll::Ready(Some(Ok(msg)));
+                    }
+                    // duplicate dropped, poll the same leg again
+                    continue;
+                }
+                Poll::Ready(Some(msg)) => {
+                    this.next = (leg + 1) % count;
+                    return Poll::Ready(Some(msg));
+                }
+                Poll::Ready(None) => closed += 1,
+                Poll::Pending => (),
+            }
+            i += 1;
+        }
+        if closed == count {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+// subscribe the same channels at every address (the same address may be repeated)
+pub async fn subscribe_redundant(
+    connect_addrs: &[&str],
+    pairs: Vec<String>,
+) -> Result<Redundant<impl Stream<Item = Result<PushMessage, PoloError>>>, PoloError> {
+    let mut legs = Vec::with_capacity(connect_addrs.len());
+    for addr in connect_addrs {
+        legs.push(Box::pin(subscribe_typed(addr, pairs.clone()).await?));
+    }
+    Ok(Redundant::new(legs))
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{Dedup, LegStats, Redundant, SEEN_WINDOW};
+    use crate::data::messages::{BookUpdate, PushMessage};
+    use futures::executor::block_on;
+    use futures::{stream, StreamExt};
+    use std::str::FromStr;
+
+    fn update(book_id: u16, record_id: u64) -> BookUpdate {
+        BookUpdate {
+            book_id,
+            record_id,
+            records: vec![],
+        }
+    }
+
+    #[test]
+    fn dedup_by_book() {
+        let mut dedup = Dedup::new(2);
+        assert!(dedup.accept(0, &update(189, 10)));
+        assert!(!dedup.accept(1, &update(189, 10)));
+        assert!(dedup.accept(1, &update(189, 11)));
+        assert!(dedup.accept(1, &update(190, 5)));
+        assert!(!dedup.accept(0, &update(189, 11)));
+        // not seen yet, forwarded once even though it is late
+        assert!(dedup.accept(0, &update(189, 9)));
+        assert!(!dedup.accept(1, &update(189, 9)));
+        assert_eq!(
+            dedup.stats(),
+            &[
+                LegStats {
+                    wins: 2,
+                    duplicates: 1
+                },
+                LegStats {
+                    wins: 2,
+                    duplicates: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_fills_gap() {
+        let mut dedup = Dedup::new(2);
+        assert!(dedup.accept(0, &update(189, 10)));
+        // leg 0 misses 11
+        assert!(dedup.accept(0, &update(189, 12)));
+        assert!(!dedup.accept(1, &update(189, 10)));
+        assert!(dedup.accept(1, &update(189, 11)));
+        assert!(!dedup.accept(1, &update(189, 12)));
+        assert!(!dedup.accept(0, &update(189, 11)));
+    }
+
+    #[test]
+    fn dedup_window() {
+        let mut dedup = Dedup::new(1);
+        for id in 1..=SEEN_WINDOW as u64 + 1 {
+            assert!(dedup.accept(0, &update(189, id)));
+        }
+        // forgotten, but older than every remembered id
+        assert!(!dedup.accept(0, &update(189, 1)));
+        assert!(!dedup.accept(0, &update(189, 2)));
+        assert_eq!(dedup.seen[&189].len(), SEEN_WINDOW);
+    }
+
+    #[test]
+    fn dedup_other_messages() {
+        let mut dedup = Dedup::new(2);
+        let heartbeat = PushMessage::Heartbeat;
+        let ticker = PushMessage::from_str(r#"[1002,null,[149,"382.98901522","381.99755898","379.41296309","-0.04312950","14969820.94951828","38859.58435407",0,"412.25844455","373.79204003"]]"#).unwrap();
+        let volume =
+            PushMessage::from_str(r#"[1003,null,["2018-11-07 16:26",5804,{"BTC":"3418.409"}]]"#)
+                .unwrap();
+        let ack = PushMessage::from_str("[1002,1]").unwrap();
+
+        assert!(dedup.accept_message(0, &heartbeat));
+        assert!(!dedup.accept_message(1, &heartbeat));
+        assert!(dedup.accept_message(0, &ticker));
+        assert!(!dedup.accept_message(1, &ticker));
+        assert!(dedup.accept_message(1, &volume));
+        assert!(!dedup.accept_message(0, &volume));
+        assert!(dedup.accept_message(0, &ack));
+        assert!(dedup.accept_message(1, &ack));
+        assert!(dedup.accept_message(1, &heartbeat));
+        assert!(!dedup.accept_message(0, &heartbeat));
+    }
+
+    #[test]
+    fn merge_legs() {
+        let leg = |ids: &[u64]| {
+            let msgs: Vec<_> = ids
+                .iter()
+                .map(|id| Ok(PushMessage::Book(update(189, *id))))
+                .chain(Some(PushMessage::from_str("[1010]")))
+                .collect();
+            stream::iter(msgs)
+        };
+        let mut merged = Redundant::new(vec![leg(&[1, 2, 3, 4]), leg(&[1, 2, 3, 4, 5])]);
+        let msgs = block_on((&mut merged).collect::<Vec<_>>());
+        let ids: Vec<_> = msgs
+            .iter()
+            .filter_map(|msg| match msg {
+                Ok(PushMessage::Book(update)) => Some(update.record_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+        assert_eq!(msgs.len(), 7);
+        let wins: u64 = merged.stats().iter().map(|stats| stats.wins).sum();
+        assert_eq!(wins, 5);
+    }
+}
@@ -0,0 +1,219 @@
+use super::reconnect::{
+    subscribe_reconnecting, ConnectionHealth, Feed, PushEvent, ReconnectOptions,
+};
+use crate::error::PoloError;
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, Default)]
+pub struct ShardOptions {
+    // max number of websocket connections, 0 is treated as 1
+    pub connections: usize,
+    // applied to every connection
+    pub reconnect: ReconnectOptions,
+}
+
+// channels spread across several reconnecting connections,
+// yields events tagged with index of the connection they came from,
+// PushEvent::Resynced carries only channels of that connection
+pub struct ShardedFeed {
+    shards: Vec<Feed>,
+    // shard of every channel, recorded before subscribe command is sent
+    // so channel in flight is not subscribed twice
+    assigned: Mutex<HashMap<String, usize>>,
+    // shard polled first next time so busy shard does not starve others
+    next: usize,
+}
+
+impl ShardedFeed {
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    // health of every connection, indexed by shard
+    pub fn health(&self) -> Vec<ConnectionHealth> {
+        self.shards.iter().map(Feed::health).collect()
+    }
+
+    // shard carrying channel if it is subscribed or its subscription is in flight
+    pub fn shard_of(&self, channel: &str) -> Option<usize> {
+        self.assigned.lock().unwrap().get(channel).copied()
+    }
+
+    // subscribe channel on the least loaded connection
+    pub fn subscribe(&self, channel: &str) -> Result<(), PoloError> {
+        let mut assigned = self.assigned.lock().unwrap();
+        if assigned.contains_key(channel) {
+            return Ok(());
+        }
+        let mut loads = vec![0; self.shards.len()];
+        for shard in assigned.values() {
+            loads[*shard] += 1;
+        }
+        let shard = least_loaded(&loads);
+        assigned.insert(channel.to_owned(), shard);
+        let subscribed = self.shards[shard].subscription().subscribe(channel);
+        if subscribed.is_err() {
+            assigned.remove(channel);
+        }
+        subscribed
+    }
+
+    pub fn unsubscribe(&self, channel: &str) -> Result<(), PoloError> {
+        let shard = self.assigned.lock().unwrap().remove(channel);
+        match shard {
+            Some(shard) => self.shards[shard].subscription().unsubscribe(channel),
+            None => Err(PoloError::from(io::Error::new(
+                io::ErrorKind::NotFound,
+                "channel is not subscribed",
+            ))),
+        }
+    }
+}
+
+impl Stream for ShardedFeed {
+    type Item = (usize, PushEvent);
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let count = self.shards.len();
+        let mut closed = 0;
+        for i in 0..count {
+            let shard = (self.next + i) % count;
+            match self.shards[shard].poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => {
+                    // rejected channel is dropped by its connection, it may be subscribed again
+                    if let PushEvent::Message(Err(PoloError::Subscription(ref channel, _))) = event
+                    {
+                        let assigned = self.assigned.get_mut().unwrap();
+                        if assigned.get(channel) == Some(&shard) {
+                            assigned.remove(channel);
+                        }
+                    }
+                    self.next = (shard + 1) % count;
+                    return Poll::Ready(Some((shard, event)));
+                }
+                Poll::Ready(None) => closed += 1,
+                Poll::Pending => (),
+            }
+        }
+        if closed == count {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+// subscribe to channels spreading them round robin across connections
+// should be called within tokio runtime
+pub fn subscribe_sharded(
+    connect_addr: &str,
+    pairs: Vec<String>,
+    options: ShardOptions,
+) -> ShardedFeed {
+    let spread = spread(pairs, options.connections);
+    let assigned = spread
+        .iter()
+        .enumerate()
+        .flat_map(|(shard, pairs)| pairs.iter().map(move |pair| (pair.clone(), shard)))
+        .collect();
+    let shards = spread
+        .into_iter()
+        .map(|pairs| subscribe_reconnecting(connect_addr, pairs, options.reconnect.clone()))
+        .collect();
+    ShardedFeed {
+        shards,
+        assigned: Mutex::new(assigned),
+        next: 0,
+    }
+}
+
+// split channels round robin, never creates more shards than channels (but at least one)
+fn spread(pairs: Vec<String>, connections: usize) -> Vec<Vec<String>> {
+    let count = connections.min(pairs.len()).max(1);
+    let mut shards = vec![Vec::new(); count];
+    for (i, pair) in pairs.into_iter().enumerate() {
+        shards[i % count].push(pair);
+    }
+    shards
+}
+
+fn least_loaded(loads: &[usize]) -> usize {
+    loads
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, load)| **load)
+        .map_or(0, |(shard, _)| shard)
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{least_loaded, spread, subscribe_sharded, ShardOptions};
+    use std::net::TcpListener;
+
+    fn pairs(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn spread_round_robin() {
+        let shards = spread(
+            pairs(&["BTC_ETH", "BTC_BCH", "USDT_BTC", "USDT_ETH", "1002"]),
+            2,
+        );
+        assert_eq!(
+            shards,
+            vec![
+                pairs(&["BTC_ETH", "USDT_BTC", "1002"]),
+                pairs(&["BTC_BCH", "USDT_ETH"])
+            ]
+        );
+    }
+
+    #[test]
+    fn spread_few_pairs() {
+        assert_eq!(spread(pairs(&["BTC_ETH"]), 4).len(), 1);
+        assert_eq!(spread(vec![], 4), vec![Vec::<String>::new()]);
+        assert_eq!(spread(pairs(&["BTC_ETH", "BTC_BCH"]), 0).len(), 1);
+    }
+
+    #[test]
+    fn subscribe_least_loaded() {
+        assert_eq!(least_loaded(&[3, 1, 2]), 1);
+        assert_eq!(least_loaded(&[2, 2]), 0);
+    }
+
+    #[tokio::test]
+    async fn subscribe_in_flight_once() {
+        // nothing listens on the port, commands stay in flight
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let options = ShardOptions {
+            connections: 2,
+            ..ShardOptions::default()
+        };
+        let feed = subscribe_sharded(
+            &format!("ws://{}", addr),
+            pairs(&["BTC_ETH", "BTC_BCH"]),
+            options,
+        );
+        feed.subscribe("USDT_BTC").unwrap();
+        feed.subscribe("USDT_BTC").unwrap();
+        feed.subscribe("USDT_ETH").unwrap();
+        assert_eq!(feed.shard_of("BTC_BCH"), Some(1));
+        assert_eq!(feed.shard_of("USDT_BTC"), Some(0));
+        assert_eq!(feed.shard_of("USDT_ETH"), Some(1));
+        feed.unsubscribe("USDT_BTC").unwrap();
+        assert_eq!(feed.shard_of("USDT_BTC"), None);
+        assert!(feed.unsubscribe("USDT_BTC").is_err());
+    }
+}
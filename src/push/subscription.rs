@@ -0,0 +1,106 @@
+use super::{message_subscribe_channel, message_unsubscribe, Credentials, Message};
+use crate::error::PoloError;
+use futures::channel::mpsc;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Command {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+// handle to add and drop channels of a live feed,
+// active channels are resubscribed after reconnect
+#[derive(Clone)]
+pub struct Subscription {
+    commands: mpsc::UnboundedSender<Command>,
+    channels: Arc<Mutex<Vec<String>>>,
+}
+
+impl Subscription {
+    pub(crate) fn new(
+        commands: mpsc::UnboundedSender<Command>,
+        channels: Arc<Mutex<Vec<String>>>,
+    ) -> Subscription {
+        Subscription { commands, channels }
+    }
+
+    pub fn subscribe(&self, channel: &str) -> Result<(), PoloError> {
+        self.send(Command::Subscribe(channel.to_owned()))
+    }
+
+    pub fn unsubscribe(&self, channel: &str) -> Result<(), PoloError> {
+        self.send(Command::Unsubscribe(channel.to_owned()))
+    }
+
+    // channels subscribed at the moment, commands still in flight are not included
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.lock().unwrap().clone()
+    }
+
+    fn send(&self, command: Command) -> Result<(), PoloError> {
+        self.commands.unbounded_send(command).map_err(|_| {
+            PoloError::from(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "push feed is closed",
+            ))
+        })
+    }
+}
+
+// update active channels returning websocket message if set was changed
+pub(crate) fn apply(
+    channels: &Mutex<Vec<String>>,
+    command: Command,
+    credentials: Option<&Credentials>,
+) -> Option<Message> {
+    let mut channels = channels.lock().unwrap();
+    match command {
+        Command::Subscribe(channel) => {
+            if channels.contains(&channel) {
+                return None;
+            }
+            let msg = message_subscribe_channel(&channel, credentials);
+            channels.push(channel);
+            Some(msg)
+        }
+        Command::Unsubscribe(channel) => {
+            let idx = channels.iter().position(|c| *c == channel)?;
+            channels.remove(idx);
+            Some(message_unsubscribe(&channel))
+        }
+    }
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, Command};
+    use std::sync::Mutex;
+
+    #[test]
+    fn apply_subscribe() {
+        let channels = Mutex::new(vec!["USDT_BTC".to_owned()]);
+        assert!(apply(&channels, Command::Subscribe("USDT_ETH".to_owned()), None).is_some());
+        assert!(apply(&channels, Command::Subscribe("USDT_BTC".to_owned()), None).is_none());
+        assert_eq!(
+            channels.into_inner().unwrap(),
+            vec!["USDT_BTC".to_owned(), "USDT_ETH".to_owned()]
+        );
+    }
+
+    #[test]
+    fn apply_unsubscribe() {
+        let channels = Mutex::new(vec!["USDT_BTC".to_owned()]);
+        assert!(apply(&channels, Command::Unsubscribe("USDT_ETH".to_owned()), None).is_none());
+        match apply(&channels, Command::Unsubscribe("USDT_BTC".to_owned()), None) {
+            Some(msg) => assert!(msg.to_text().unwrap().contains("unsubscribe")),
+            None => panic!("unsubscribe message was not produced"),
+        }
+        assert!(channels.into_inner().unwrap().is_empty());
+    }
+}
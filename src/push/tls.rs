@@ -0,0 +1,257 @@
+use super::WsStream;
+use std::fmt;
+use tokio::net::TcpStream;
+use tungstenite::error::Error;
+use url::Url;
+
+// accepting any certificate must not leak into a shipped binary
+#[cfg(all(feature = "insecure-tls", not(debug_assertions)))]
+compile_error!("insecure-tls feature disables certificate checks, it is refused in release builds");
+
+// certificate trusted as root authority
+#[derive(Clone, Debug, PartialEq)]
+pub enum Certificate {
+    Pem(Vec<u8>),
+    Der(Vec<u8>),
+}
+
+// client certificate with its private key
+#[derive(Clone, PartialEq)]
+pub struct Identity {
+    // DER encoded PKCS #12 archive
+    pub pkcs12: Vec<u8>,
+    pub password: String,
+}
+
+// TLS settings of wss:// connections, default is the system trust store
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlsConfig {
+    // trusted in addition to system roots, e.g. certificate of own TLS-terminating relay
+    pub root_certificates: Vec<Certificate>,
+    // trust root_certificates only
+    pub only_custom_roots: bool,
+    pub identity: Option<Identity>,
+    // refuse plain ws:// urls, they are allowed by default for local mocks
+    pub require_tls: bool,
+    // accept any server certificate and host name, never use outside of tests,
+    // exists only in debug builds with insecure-tls feature
+    #[cfg(feature = "insecure-tls")]
+    pub danger_accept_invalid_certs: bool,
+}
+
+// keep passwords out of logs
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Identity")
+            .field("pkcs12", &format!("<{} bytes>", self.pkcs12.len()))
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    // true if connector of tokio-tungstenite with system roots is not enough
+    pub fn is_custom(&self) -> bool {
+        !self.root_certificates.is_empty()
+            || self.only_custom_roots
+            || self.identity.is_some()
+            || self.insecure()
+    }
+
+    #[cfg(feature = "insecure-tls")]
+    fn insecure(&self) -> bool {
+        self.danger_accept_invalid_certs
+    }
+
+    #[cfg(not(feature = "insecure-tls"))]
+    fn insecure(&self) -> bool {
+        false
+    }
+}
+
+// TLS handshake over established tcp stream followed by websocket handshake
+#[cfg(feature = "tls")]
+pub(crate) async fn connect_tls(
+    url: Url,
+    host: &str,
+    stream: TcpStream,
+    config: &TlsConfig,
+) -> Result<WsStream, Error> {
+    use std::io;
+    use tokio_tungstenite::{client_async, stream::Stream};
+
+    let tls_error = |err| Error::Io(io::Error::new(io::ErrorKind::ConnectionRefused, err));
+    let connector = tokio_tls::TlsConnector::from(connector(config).map_err(tls_error)?);
+    let stream = connector.connect(host, stream).await.map_err(tls_error)?;
+    Ok(client_async(url, Stream::Tls(stream)).await?.0)
+}
+
+#[cfg(not(feature = "tls"))]
+pub(crate) async fn connect_tls(
+    _url: Url,
+    _host: &str,
+    _stream: TcpStream,
+    _config: &TlsConfig,
+) -> Result<WsStream, Error> {
+    Err(Error::Url("custom TLS settings require tls feature".into()))
+}
+
+#[cfg(feature = "tls")]
+fn connector(config: &TlsConfig) -> Result<native_tls::TlsConnector, native_tls::Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    for certificate in config.root_certificates.iter() {
+        let certificate = match certificate {
+            Certificate::Pem(pem) => native_tls::Certificate::from_pem(pem)?,
+            Certificate::Der(der) => native_tls::Certificate::from_der(der)?,
+        };
+        builder.add_root_certificate(certificate);
+    }
+    builder.disable_built_in_roots(config.only_custom_roots);
+    if let Some(ref identity) = config.identity {
+        builder.identity(native_tls::Identity::from_pkcs12(
+            &identity.pkcs12,
+            &identity.password,
+        )?);
+    }
+    #[cfg(feature = "insecure-tls")]
+    builder
+        .danger_accept_invalid_certs(config.danger_accept_invalid_certs)
+        .danger_accept_invalid_hostnames(config.danger_accept_invalid_certs);
+    builder.build()
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{Certificate, Identity, TlsConfig};
+    use crate::mock::MockServer;
+    use crate::push::{self, ConnectOptions};
+
+    #[test]
+    fn custom_config() {
+        assert!(!TlsConfig::default().is_custom());
+        let config = TlsConfig {
+            root_certificates: vec![Certificate::Pem(b"-----BEGIN CERTIFICATE-----".to_vec())],
+            ..TlsConfig::default()
+        };
+        assert!(config.is_custom());
+        let config = TlsConfig {
+            require_tls: true,
+            ..TlsConfig::default()
+        };
+        assert!(!config.is_custom());
+    }
+
+    #[test]
+    fn debug_hides_identity() {
+        let identity = Identity {
+            pkcs12: vec![1, 2, 3],
+            password: "secret".to_owned(),
+        };
+        let debug = format!("{:?}", identity);
+        assert!(!debug.contains("secret"));
+        assert!(debug.contains("<3 bytes>"));
+    }
+
+    // localhost.p12 (password poloniex) holds certificate for localhost and 127.0.0.1
+    // issued by root.pem, both made with openssl req/x509/pkcs12 for 100 years
+    #[cfg(feature = "tls")]
+    async fn tls_listener() -> u16 {
+        use futures::SinkExt;
+        use tokio::net::TcpListener;
+        use tungstenite::Message;
+
+        let pkcs12 = include_bytes!("testdata/localhost.p12");
+        let identity = native_tls::Identity::from_pkcs12(pkcs12, "poloniex").unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let acceptor = tokio_tls::TlsAcceptor::from(acceptor);
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // handshake fails when client does not trust the certificate
+            if let Ok(stream) = acceptor.accept(stream).await {
+                let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+                ws_stream.send(Message::text("[1010]")).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn custom_root_trusted() {
+        use futures::StreamExt;
+        use tungstenite::Message;
+
+        let port = tls_listener().await;
+        let options = ConnectOptions {
+            tls: TlsConfig {
+                root_certificates: vec![Certificate::Pem(
+                    include_bytes!("testdata/root.pem").to_vec(),
+                )],
+                only_custom_roots: true,
+                require_tls: true,
+                ..TlsConfig::default()
+            },
+            ..ConnectOptions::default()
+        };
+        let url = format!("wss://localhost:{}", port);
+        let mut stream = push::subscribe_with_options(&url, vec![], None, &options)
+            .await
+            .unwrap();
+        let msg = stream.next().await.unwrap().unwrap();
+        assert_eq!(msg, Message::text("[1010]"));
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn unknown_root_refused() {
+        let port = tls_listener().await;
+        let url = format!("wss://localhost:{}", port);
+        let stream = push::subscribe(&url, vec![]).await;
+        assert!(stream.is_err());
+    }
+
+    #[cfg(feature = "insecure-tls")]
+    #[tokio::test]
+    async fn invalid_certs_accepted() {
+        let port = tls_listener().await;
+        let options = ConnectOptions {
+            tls: TlsConfig {
+                danger_accept_invalid_certs: true,
+                ..TlsConfig::default()
+            },
+            ..ConnectOptions::default()
+        };
+        let url = format!("wss://localhost:{}", port);
+        let stream = push::subscribe_with_options(&url, vec![], None, &options).await;
+        assert!(stream.is_ok());
+    }
+
+    #[tokio::test]
+    async fn plain_allowed() {
+        let server = MockServer::start(vec![]).await.unwrap();
+        let options = ConnectOptions::default();
+        let stream = push::subscribe_with_options(&server.url(), vec![], None, &options).await;
+        assert!(stream.is_ok());
+    }
+
+    #[tokio::test]
+    async fn plain_refused() {
+        let server = MockServer::start(vec![]).await.unwrap();
+        let options = ConnectOptions {
+            tls: TlsConfig {
+                require_tls: true,
+                ..TlsConfig::default()
+            },
+            ..ConnectOptions::default()
+        };
+        let stream = push::subscribe_with_options(&server.url(), vec![], None, &options).await;
+        assert!(stream.is_err());
+        assert_eq!(server.connections(), 0);
+    }
+}
@@ -0,0 +1,94 @@
+use super::connect::{connect, ConnectOptions};
+use super::{Message, WsStream};
+use crate::data::v3::V3Message;
+use crate::error::PoloError;
+use futures::{future, SinkExt, Stream, StreamExt};
+use std::str::FromStr;
+use tungstenite::error::Error;
+
+// subscribe to v3 channels (ex: book_lv2, trades) of given symbols (ex: BTC_USDT),
+// server drops connections without a ping for 30 seconds, see message_ping_v3
+pub async fn subscribe_v3(
+    connect_addr: &str,
+    channels: Vec<String>,
+    symbols: Vec<String>,
+    options: &ConnectOptions,
+) -> Result<WsStream, Error> {
+    let mut ws_stream = connect(connect_addr, options).await?;
+    ws_stream
+        .send(message_subscribe_v3(&channels, &symbols))
+        .await?;
+    Ok(ws_stream)
+}
+
+// parse v3 websocket frames of the stream skipping control frames
+pub fn typed_v3<S>(stream: S) -> impl Stream<Item = Result<V3Message, PoloError>>
+where
+    S: Stream<Item = Result<Message, Error>>,
+{
+    stream.filter_map(|msg| {
+        future::ready(match msg {
+            Ok(msg) => decode_v3(msg),
+            Err(err) => Some(Err(PoloError::from(err))),
+        })
+    })
+}
+
+// parse v3 text frame, on failure raw text is kept in PoloError::Decode
+pub fn decode_v3(msg: Message) -> Option<Result<V3Message, PoloError>> {
+    match msg {
+        Message::Text(text) => {
+            Some(V3Message::from_str(&text).map_err(|err| PoloError::Decode(Box::new(err), text)))
+        }
+        _ => None,
+    }
+}
+
+// keep alive command, answered with V3Message::Pong
+pub fn message_ping_v3() -> Message {
+    Message::text("{\"event\": \"ping\"}")
+}
+
+pub fn message_subscribe_v3(channels: &[String], symbols: &[String]) -> Message {
+    message_v3("subscribe", channels, symbols)
+}
+
+pub fn message_unsubscribe_v3(channels: &[String], symbols: &[String]) -> Message {
+    message_v3("unsubscribe", channels, symbols)
+}
+
+fn message_v3(event: &str, channels: &[String], symbols: &[String]) -> Message {
+    let command = json::object! {
+        "event" => event,
+        "channel" => channels.to_vec(),
+        "symbols" => symbols.to_vec(),
+    };
+    Message::text(command.dump())
+}
+
+/**
+ ** TESTS TESTS TESTS
+ **/
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_v3, message_subscribe_v3, Message};
+    use crate::data::v3::V3Message;
+
+    #[test]
+    fn subscribe_command() {
+        let msg = message_subscribe_v3(&["book_lv2".to_owned()], &["BTC_USDT".to_owned()]);
+        let command = json::parse(msg.to_text().unwrap()).unwrap();
+        assert_eq!(command["event"], "subscribe");
+        assert_eq!(command["channel"][0], "book_lv2");
+        assert_eq!(command["symbols"][0], "BTC_USDT");
+    }
+
+    #[test]
+    fn decode_pong() {
+        match decode_v3(Message::text(r#"{"event":"pong"}"#)) {
+            Some(Ok(V3Message::Pong)) => (),
+            msg => panic!("expected pong {:?}", msg),
+        }
+    }
+}